@@ -0,0 +1,616 @@
+// Card abilities for Magic: The Gathering Amulet Titan simulation
+
+use crate::cards::{card_type, Card, CardType, HasManaValue, Land, ManaValue, Permanent, Spell};
+use crate::game_state::{
+    AbilitySource, GameObject, GameObjectId, GameState, ManaPool, PlayerId, StackObject, TapState, Target,
+    Trigger,
+};
+use crate::mana::ManaType;
+
+// ============================================================================
+// MANA ABILITIES
+// ============================================================================
+
+/// A "{cost}, {T}: Add {produces}" ability of a land
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManaAbility {
+    pub cost: ManaValue,
+    pub produces: ManaPool,
+}
+
+impl ManaAbility {
+    /// A plain "{T}: Add ..." ability with no additional cost
+    fn tap_for(produces: ManaPool) -> Self {
+        ManaAbility {
+            cost: ManaValue::default(),
+            produces,
+        }
+    }
+}
+
+/// Returns every mana ability a land has, one entry per distinct choice of
+/// mana it can produce
+pub fn mana_abilities(land: Land) -> Vec<ManaAbility> {
+    match land {
+        Land::Forest | Land::BoseijuWhoEndures | Land::ShiftingWoodland => {
+            vec![ManaAbility::tap_for(ManaPool::of(ManaType::Green, 1))]
+        }
+        Land::TolariaWest | Land::OtawaraSoaringCity => {
+            vec![ManaAbility::tap_for(ManaPool::of(ManaType::Blue, 1))]
+        }
+        Land::CrumblingVestige
+        | Land::EchoingDeeps
+        | Land::HanweirBattlements
+        | Land::Mirrorpool
+        | Land::UrzasCave
+        | Land::UrzasSaga => {
+            vec![ManaAbility::tap_for(ManaPool::of(ManaType::Colorless, 1))]
+        }
+        Land::GruulTurf => vec![ManaAbility::tap_for(ManaPool {
+            red: 1,
+            green: 1,
+            ..ManaPool::default()
+        })],
+        Land::SimicGrowthChamber => vec![ManaAbility::tap_for(ManaPool {
+            blue: 1,
+            green: 1,
+            ..ManaPool::default()
+        })],
+        Land::LotusField => ManaType::COLORS
+            .into_iter()
+            .map(|color| ManaAbility::tap_for(ManaPool::of(color, 3)))
+            .collect(),
+        Land::TheMycosynthGardens => {
+            let filter_cost = ManaValue {
+                generic: 1,
+                ..ManaValue::default()
+            };
+            let mut abilities = vec![ManaAbility::tap_for(ManaPool::of(ManaType::Colorless, 1))];
+            abilities.extend(ManaType::COLORS.into_iter().map(|color| ManaAbility {
+                cost: filter_cost.clone(),
+                produces: ManaPool::of(color, 1),
+            }));
+            abilities
+        }
+        // Vesuva only has the abilities of the land it copies
+        Land::Vesuva => Vec::new(),
+    }
+}
+
+impl GameState {
+    /// Returns the mana abilities that can be activated for an untapped land
+    /// controlled by the active player, given the current mana pool
+    pub fn available_mana_abilities(&self, source: GameObjectId) -> Vec<ManaAbility> {
+        let Some(land) = self.active_player.battlefield.lands.get(&source) else {
+            return Vec::new();
+        };
+        // A land that became a copy of a non-land has no mana abilities
+        let Card::Land(copied) = land.card() else {
+            return Vec::new();
+        };
+        if land.tap_state == TapState::Tapped {
+            return Vec::new();
+        }
+        // Vesuva and Echoing Deeps are still choosing what to enter as
+        let choosing_copy = StackObject::Trigger(Trigger::EntersAsCopy(source));
+        if self.stack.objects.contains(&choosing_copy) {
+            return Vec::new();
+        }
+        mana_abilities(copied)
+            .into_iter()
+            .filter(|ability| self.active_player.mana_pool.can_pay(&ability.cost, 0))
+            .collect()
+    }
+}
+
+// ============================================================================
+// ENTERING THE BATTLEFIELD
+// ============================================================================
+
+/// Returns true for karoo lands, which bounce a land when they enter
+pub fn is_karoo(land: Land) -> bool {
+    matches!(land, Land::GruulTurf | Land::SimicGrowthChamber)
+}
+
+/// Returns true if a land always enters the battlefield tapped. Vesuva and
+/// Echoing Deeps only enter tapped if they copy something, which is decided
+/// once they're on the battlefield.
+pub fn enters_tapped(land: Land) -> bool {
+    is_karoo(land)
+        || matches!(
+            land,
+            Land::CrumblingVestige | Land::LotusField | Land::Mirrorpool | Land::TolariaWest
+        )
+}
+
+/// Returns the "when this enters" trigger printed on a card, if it has one
+pub fn enter_trigger(card: Card) -> Option<Trigger> {
+    let has_enter_trigger = match card {
+        Card::Land(land) => is_karoo(land) || matches!(land, Land::CrumblingVestige | Land::LotusField),
+        Card::Spell(Spell::Permanent(permanent)) => matches!(
+            permanent,
+            Permanent::PrimevalTitan
+                | Permanent::AftermathAnalyst
+                | Permanent::Spelunking
+                | Permanent::ArborealGrazer
+                | Permanent::CultivatorColossus
+        ),
+        Card::Spell(Spell::NonPermanent(_)) => false,
+    };
+    has_enter_trigger.then_some(Trigger::Enters(card))
+}
+
+/// Urza's Saga sacrifices itself once its third chapter resolves
+pub const SAGA_FINAL_CHAPTER: u8 = 3;
+
+impl GameState {
+    /// Returns the tap state a card actually enters with when an effect asks
+    /// for `requested`
+    pub fn entering_tap_state(&self, card: Card, requested: TapState) -> TapState {
+        match card {
+            // Spelunking replaces every other reason for a land to enter tapped
+            Card::Land(_) if self.controls(Permanent::Spelunking) => TapState::Untapped,
+            Card::Land(land) if enters_tapped(land) => TapState::Tapped,
+            Card::Land(Land::ShiftingWoodland) if !self.controls_card(Card::Land(Land::Forest)) => {
+                TapState::Tapped
+            }
+            _ => requested,
+        }
+    }
+
+    /// Returns true if the active player controls a non-land permanent
+    pub fn controls(&self, permanent: Permanent) -> bool {
+        self.controls_card(Card::Spell(Spell::Permanent(permanent)))
+    }
+
+    /// Returns true if the active player controls a permanent that currently
+    /// is the card, following copy effects
+    pub fn controls_card(&self, card: Card) -> bool {
+        self.active_player.battlefield.cards().any(|(_, c)| c == card)
+    }
+
+    /// Applies the rules that modify how an object enters the battlefield
+    pub fn entering_object(&self, object: GameObject<Card>) -> GameObject<Card> {
+        let mut entering = GameObject {
+            tap_state: self.entering_tap_state(object.card(), object.tap_state),
+            entered_turn: self.turn.number,
+            ..object
+        };
+        // Sagas enter with their first lore counter; chapter I only grants the
+        // mana ability, which follows the lore count directly
+        if entering.card() == Card::Land(Land::UrzasSaga) {
+            entering.lore_counters = 1;
+        }
+        entering
+    }
+
+    /// Returns the Amulet of Vigor objects the active player controls,
+    /// including copies of it, in id order
+    pub fn amulets(&self) -> Vec<GameObjectId> {
+        let mut amulets: Vec<GameObjectId> = self
+            .active_player
+            .battlefield
+            .cards()
+            .filter(|&(_, card)| card == Card::Spell(Spell::Permanent(Permanent::AmuletOfVigor)))
+            .map(|(id, _)| id)
+            .collect();
+        amulets.sort();
+        amulets
+    }
+
+    /// Returns the triggers caused by a permanent that just entered the
+    /// battlefield, in the order they go on the stack
+    pub fn enter_triggers(&self, id: GameObjectId) -> Vec<Trigger> {
+        let mut triggers = Vec::new();
+        if let Some(card) = self.permanent(id) {
+            // Vesuva and Echoing Deeps pick what to copy first, so the copy's
+            // own enter trigger goes on the stack once it has been chosen
+            if matches!(card, Card::Land(Land::Vesuva | Land::EchoingDeeps)) {
+                triggers.push(Trigger::EntersAsCopy(id));
+            }
+            triggers.extend(enter_trigger(card));
+        }
+        // Each Amulet triggers separately; putting them on top lets the
+        // permanent untap before its own enter triggers resolve
+        if self.active_player.battlefield.tap_state(id) == Some(TapState::Tapped) {
+            triggers.extend(self.amulets().into_iter().map(|_| Trigger::AmuletUntap(id)));
+        }
+        triggers
+    }
+}
+
+// ============================================================================
+// ATTACKING
+// ============================================================================
+
+impl GameState {
+    /// Returns the triggers caused by a creature attacking
+    pub fn attack_triggers(&self, id: GameObjectId) -> Vec<Trigger> {
+        match self.permanent(id) {
+            Some(card) if card == Card::Spell(Spell::Permanent(Permanent::PrimevalTitan)) => {
+                vec![Trigger::Attacks(card)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns a creature's power. Cultivator Colossus counts the lands and
+    /// Construct tokens the artifacts the active player controls.
+    pub fn power(&self, id: GameObjectId) -> usize {
+        let Some(Card::Spell(Spell::Permanent(permanent))) = self.permanent(id) else {
+            return 0;
+        };
+        let controlled = |required: CardType| {
+            self.active_player
+                .battlefield
+                .cards()
+                .filter(|&(_, card)| card_type(card).contains(required))
+                .count()
+        };
+        match permanent {
+            Permanent::PrimevalTitan => 6,
+            Permanent::AftermathAnalyst => 1,
+            Permanent::CultivatorColossus => controlled(CardType::LAND),
+            Permanent::ConstructToken => controlled(CardType::ARTIFACT),
+            Permanent::AmuletOfVigor | Permanent::Spelunking | Permanent::ArborealGrazer => 0,
+        }
+    }
+}
+
+// ============================================================================
+// SAGAS
+// ============================================================================
+
+impl GameState {
+    /// Returns the Sagas the active player controls, in id order
+    pub fn sagas(&self) -> Vec<GameObjectId> {
+        let mut sagas: Vec<GameObjectId> = self
+            .active_player
+            .battlefield
+            .lands
+            .iter()
+            .filter(|(_, object)| object.card() == Card::Land(Land::UrzasSaga))
+            .map(|(&id, _)| id)
+            .collect();
+        sagas.sort();
+        sagas
+    }
+
+    /// Returns the chapter trigger caused by adding a lore counter to a Saga.
+    /// Only the final chapter uses the stack, since the earlier chapters just
+    /// grant abilities.
+    pub fn chapter_trigger(&self, source: GameObjectId, lore_counters: u8) -> Option<Trigger> {
+        (lore_counters == SAGA_FINAL_CHAPTER).then_some(Trigger::Chapter {
+            source,
+            chapter: lore_counters,
+        })
+    }
+}
+
+// ============================================================================
+// ACTIVATED ABILITIES
+// ============================================================================
+
+/// The effect of a non-mana activated ability once it's on the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ability {
+    /// Urza's Saga chapter II: create a Construct token
+    CreateConstruct,
+    /// Aftermath Analyst: return all land cards from your graveyard to the
+    /// battlefield tapped
+    ReturnLandsFromGraveyard,
+    /// Boseiju channel: destroy target artifact, enchantment or nonbasic land
+    /// an opponent controls; they may search for a land with a basic land type
+    DestroyPermanent,
+    /// Otawara channel: return target artifact, creature or enchantment to
+    /// its owner's hand
+    ReturnPermanentToHand,
+    /// Tolaria West transmute: search for a card with the given mana value
+    /// and put it into your hand
+    Transmute { mana_value: usize },
+    /// Mirrorpool: create a token that's a copy of target land you control
+    CreateTokenCopy,
+    /// Urza's Cave: search for a land card and put it on top of your library
+    SearchLandToTop,
+    /// Hanweir Battlements: target creature gains haste until end of turn
+    GrantHaste,
+    /// The Mycosynth Gardens: become a copy of target nontoken artifact you
+    /// control with mana value X
+    CopyArtifact,
+    /// Shifting Woodland: become a copy of target permanent card in your
+    /// graveyard until end of turn
+    CopyUntilEndOfTurn,
+}
+
+/// Returns true for lands with the supertype basic
+pub fn is_basic(land: Land) -> bool {
+    matches!(land, Land::Forest)
+}
+
+/// A non-mana activated ability and the costs to activate it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivatedAbility {
+    pub ability: Ability,
+    pub cost: ManaValue,
+    pub tap: bool,
+    pub sacrifice: bool,
+    pub discard: bool,
+    pub sorcery_speed: bool,
+}
+
+impl GameState {
+    /// Returns the activated ability of a permanent the active player
+    /// controls or a card in their hand, if it currently has one
+    pub fn activated_ability(&self, source: AbilitySource) -> Option<ActivatedAbility> {
+        let source = match source {
+            AbilitySource::Battlefield(id) => id,
+            AbilitySource::Hand(card) => return self.hand_ability(card),
+        };
+        let battlefield = &self.active_player.battlefield;
+        if let Some(land) = battlefield.lands.get(&source) {
+            return match land.card() {
+                Card::Land(Land::UrzasSaga) if land.lore_counters >= 2 => Some(ActivatedAbility {
+                    ability: Ability::CreateConstruct,
+                    cost: ManaValue {
+                        generic: 2,
+                        ..ManaValue::default()
+                    },
+                    tap: true,
+                    sacrifice: false,
+                    discard: false,
+                    sorcery_speed: false,
+                }),
+                Card::Land(Land::Mirrorpool) => Some(ActivatedAbility {
+                    ability: Ability::CreateTokenCopy,
+                    cost: ManaValue {
+                        generic: 4,
+                        ..ManaValue::default()
+                    },
+                    tap: true,
+                    sacrifice: true,
+                    discard: false,
+                    sorcery_speed: true,
+                }),
+                Card::Land(Land::UrzasCave) => Some(ActivatedAbility {
+                    ability: Ability::SearchLandToTop,
+                    cost: ManaValue {
+                        generic: 3,
+                        ..ManaValue::default()
+                    },
+                    tap: true,
+                    sacrifice: true,
+                    discard: false,
+                    sorcery_speed: false,
+                }),
+                Card::Land(Land::HanweirBattlements) => Some(ActivatedAbility {
+                    ability: Ability::GrantHaste,
+                    cost: ManaValue {
+                        red: 2,
+                        green: 1,
+                        ..ManaValue::default()
+                    },
+                    tap: true,
+                    sacrifice: false,
+                    discard: false,
+                    sorcery_speed: false,
+                }),
+                // X is always 1: Amulet of Vigor is the only nontoken artifact
+                // the deck has to copy
+                Card::Land(Land::TheMycosynthGardens) => Some(ActivatedAbility {
+                    ability: Ability::CopyArtifact,
+                    cost: ManaValue {
+                        generic: 1,
+                        ..ManaValue::default()
+                    },
+                    tap: true,
+                    sacrifice: false,
+                    discard: false,
+                    sorcery_speed: false,
+                }),
+                Card::Land(Land::ShiftingWoodland) if self.active_player.graveyard.has_delirium() => {
+                    Some(ActivatedAbility {
+                        ability: Ability::CopyUntilEndOfTurn,
+                        cost: ManaValue {
+                            green: 2,
+                            generic: 2,
+                            ..ManaValue::default()
+                        },
+                        tap: false,
+                        sacrifice: false,
+                        discard: false,
+                        sorcery_speed: false,
+                    })
+                }
+                _ => None,
+            };
+        }
+        match battlefield.non_lands.get(&source)?.card() {
+            Card::Spell(Spell::Permanent(Permanent::AftermathAnalyst)) => Some(ActivatedAbility {
+                ability: Ability::ReturnLandsFromGraveyard,
+                cost: ManaValue {
+                    green: 1,
+                    generic: 3,
+                    ..ManaValue::default()
+                },
+                tap: false,
+                sacrifice: true,
+                discard: false,
+                sorcery_speed: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the channel or transmute ability of a card in the active
+    /// player's hand
+    fn hand_ability(&self, card: Card) -> Option<ActivatedAbility> {
+        if !self.active_player.hand.contains(card) {
+            return None;
+        }
+        let (ability, cost, sorcery_speed) = match card {
+            Card::Land(Land::BoseijuWhoEndures) => (
+                Ability::DestroyPermanent,
+                ManaValue {
+                    green: 1,
+                    generic: 1,
+                    ..ManaValue::default()
+                },
+                false,
+            ),
+            Card::Land(Land::OtawaraSoaringCity) => (
+                Ability::ReturnPermanentToHand,
+                ManaValue {
+                    blue: 1,
+                    generic: 3,
+                    ..ManaValue::default()
+                },
+                false,
+            ),
+            // Transmute can only be activated as a sorcery
+            Card::Land(Land::TolariaWest) => (
+                Ability::Transmute { mana_value: 0 },
+                ManaValue {
+                    blue: 2,
+                    generic: 1,
+                    ..ManaValue::default()
+                },
+                true,
+            ),
+            _ => return None,
+        };
+        Some(ActivatedAbility {
+            ability,
+            cost,
+            tap: false,
+            sacrifice: false,
+            discard: true,
+            sorcery_speed,
+        })
+    }
+
+    /// Returns true if no decision is pending, the active player can pay every
+    /// cost of the ability with the right timing and its target is legal
+    pub fn can_activate(
+        &self,
+        source: AbilitySource,
+        ability: &ActivatedAbility,
+        target: Option<&Target>,
+    ) -> bool {
+        let untapped = match source {
+            AbilitySource::Battlefield(id) => {
+                self.active_player.battlefield.tap_state(id) == Some(TapState::Untapped)
+            }
+            AbilitySource::Hand(_) => false,
+        };
+        self.decision.is_none()
+            && (!ability.tap || untapped)
+            && (!ability.sorcery_speed || self.is_sorcery_speed())
+            && self.active_player.mana_pool.can_pay(&ability.cost, 0)
+            && self.is_legal_target(ability.ability, target)
+    }
+
+    /// Returns true if the target is legal for the ability. Abilities without
+    /// a target accept anything.
+    pub fn is_legal_target(&self, ability: Ability, target: Option<&Target>) -> bool {
+        match (ability, target) {
+            (
+                Ability::CreateConstruct
+                | Ability::ReturnLandsFromGraveyard
+                | Ability::Transmute { .. }
+                | Ability::SearchLandToTop,
+                _,
+            ) => true,
+            (Ability::CopyUntilEndOfTurn, Some(&Target::Card(card))) => {
+                !card_type(card).intersects(CardType::SORCERY | CardType::INSTANT)
+                    && self.active_player.graveyard.iter().any(|c| c == card)
+            }
+            (_, Some(&Target::Object(id))) => self.is_legal_object_target(ability, id),
+            _ => false,
+        }
+    }
+
+    /// Returns true if a permanent on either battlefield is a legal target
+    /// for the ability
+    fn is_legal_object_target(&self, ability: Ability, id: GameObjectId) -> bool {
+        let (Some(controller), Some(card)) = (self.controller(id), self.permanent(id)) else {
+            return false;
+        };
+        let types = card_type(card);
+        let yours = controller == PlayerId::Active;
+        match ability {
+            Ability::DestroyPermanent => {
+                let nonbasic_land = matches!(card, Card::Land(land) if !is_basic(land));
+                !yours && (types.intersects(CardType::ARTIFACT | CardType::ENCHANTMENT) || nonbasic_land)
+            }
+            Ability::ReturnPermanentToHand => {
+                types.intersects(CardType::ARTIFACT | CardType::CREATURE | CardType::ENCHANTMENT)
+            }
+            Ability::CreateTokenCopy => yours && types.contains(CardType::LAND),
+            Ability::GrantHaste => types.contains(CardType::CREATURE),
+            Ability::CopyArtifact => {
+                let battlefield = &self.active_player.battlefield;
+                let token = battlefield
+                    .lands
+                    .get(&id)
+                    .map(|land| land.token)
+                    .or_else(|| battlefield.non_lands.get(&id).map(|permanent| permanent.token))
+                    .unwrap_or(false);
+                yours && !token && types.contains(CardType::ARTIFACT) && card.mana_value().converted() == 1
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_action::GameAction;
+
+    #[test]
+    fn untapped_lands_tap_for_their_mana() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let forest = game_state.add_test_land(Land::Forest, TapState::Untapped);
+        let turf = game_state.add_test_land(Land::GruulTurf, TapState::Tapped);
+        assert_eq!(
+            game_state.available_mana_abilities(forest),
+            vec![ManaAbility::tap_for(ManaPool::of(ManaType::Green, 1))]
+        );
+        assert!(game_state.available_mana_abilities(turf).is_empty());
+        assert_eq!(mana_abilities(Land::LotusField).len(), 5);
+    }
+
+    #[test]
+    fn filter_abilities_need_mana_in_the_pool() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let gardens = game_state.add_test_land(Land::TheMycosynthGardens, TapState::Untapped);
+        assert_eq!(game_state.available_mana_abilities(gardens).len(), 1);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Colorless, 1);
+        assert_eq!(game_state.available_mana_abilities(gardens).len(), 6);
+    }
+
+    #[test]
+    fn activating_a_filter_ability_and_reverting_it() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let gardens = game_state.add_test_land(Land::TheMycosynthGardens, TapState::Untapped);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Colorless, 1);
+        let ability = game_state.available_mana_abilities(gardens).pop().unwrap();
+        let result = GameAction::ActivateManaAbility { source: gardens, ability }.apply(&mut game_state);
+        assert_eq!(game_state.active_player.mana_pool, ManaPool::of(ManaType::Green, 1));
+        assert_eq!(game_state.active_player.battlefield.tap_state(gardens), Some(TapState::Tapped));
+        result.revert(&mut game_state);
+        assert_eq!(game_state.active_player.mana_pool, ManaPool::of(ManaType::Colorless, 1));
+        assert_eq!(game_state.active_player.battlefield.tap_state(gardens), Some(TapState::Untapped));
+    }
+
+    #[test]
+    fn unavailable_mana_abilities_do_nothing() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let forest = game_state.add_test_land(Land::Forest, TapState::Tapped);
+        let ability = ManaAbility::tap_for(ManaPool::of(ManaType::Green, 1));
+        let result = GameAction::ActivateManaAbility { source: forest, ability }.apply(&mut game_state);
+        assert!(game_state.active_player.mana_pool.is_empty());
+        result.revert(&mut game_state);
+        assert_eq!(game_state.active_player.battlefield.tap_state(forest), Some(TapState::Tapped));
+    }
+}
@@ -1,5 +1,8 @@
 // Magic: The Gathering card enums for Amulet Titan deck simulation
 
+// The Enum derive for NonPermanent takes a modulo by Instant's single-variant length
+#![allow(clippy::modulo_one)]
+
 use bitflags::bitflags;
 use enum_map::Enum;
 
@@ -24,7 +27,7 @@ bitflags! {
 // ============================================================================
 
 /// Mana value representation with individual mana costs
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct ManaValue {
     pub white: u8,
     pub blue: u8,
@@ -36,23 +39,33 @@ pub struct ManaValue {
     pub x: u8,
 }
 
+impl ManaValue {
+    /// Returns the converted mana value, counting X as zero
+    pub fn converted(&self) -> usize {
+        [self.white, self.blue, self.black, self.red, self.green, self.colorless, self.generic]
+            .iter()
+            .map(|&pips| pips as usize)
+            .sum()
+    }
+}
+
 // ============================================================================
 // MAIN CARD ENUM
 // ============================================================================
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum Card {
     Land(Land),
     Spell(Spell),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum Spell {
     Permanent(Permanent),
     NonPermanent(NonPermanent),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum Permanent {
     // Artifacts
     AmuletOfVigor,
@@ -65,27 +78,30 @@ pub enum Permanent {
     ArborealGrazer,
     CultivatorColossus,
     PrimevalTitan,
+
+    // Tokens
+    ConstructToken,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum NonPermanent {
     Sorcery(Sorcery),
     Instant(Instant),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum Sorcery {
     Explore,
     GreenSunsZenith,
     Scapeshift,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum Instant {
     SummonersPact,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
 pub enum Land {
     BoseijuWhoEndures,
     CrumblingVestige,
@@ -140,6 +156,9 @@ pub const fn card_type(card: Card) -> CardType {
                         // Creatures
                         Permanent::AftermathAnalyst | Permanent::ArborealGrazer |
                         Permanent::CultivatorColossus | Permanent::PrimevalTitan => CardType::CREATURE,
+
+                        // Tokens
+                        Permanent::ConstructToken => CardType::ARTIFACT.union(CardType::CREATURE),
                     }
                 }
                 Spell::NonPermanent(non_permanent) => {
@@ -165,6 +184,22 @@ pub const fn card_type(card: Card) -> CardType {
 // HAS MANA VALUE TRAIT IMPLEMENTATIONS
 // ============================================================================
 
+impl HasManaValue for Card {
+    fn mana_value(&self) -> ManaValue {
+        match self {
+            Card::Land(land) => land.mana_value(),
+            Card::Spell(spell) => spell.mana_value(),
+        }
+    }
+}
+
+impl HasManaValue for Land {
+    // Lands have no mana cost
+    fn mana_value(&self) -> ManaValue {
+        ManaValue::default()
+    }
+}
+
 impl HasManaValue for Spell {
     fn mana_value(&self) -> ManaValue {
         match self {
@@ -206,6 +241,12 @@ impl HasManaValue for Permanent {
                 white: 0, blue: 0, black: 0, red: 0, green: 2,
                 colorless: 0, generic: 4, x: 0
             },
+
+            // Tokens
+            Permanent::ConstructToken => ManaValue {
+                white: 0, blue: 0, black: 0, red: 0, green: 0,
+                colorless: 0, generic: 0, x: 0
+            },
         }
     }
 }
@@ -2,9 +2,10 @@
 
 use crate::game_state::*;
 use crate::cards::*;
+use crate::abilities::{Ability, ManaAbility};
 
 /// Trait for game actions that can be applied and reverted
-
+#[derive(Debug, Clone, PartialEq)]
 pub enum PrimitiveGameAction {
     DrawCards(usize),
     MillCards(usize),
@@ -12,35 +13,163 @@ pub enum PrimitiveGameAction {
     IncreaseLandPlays(usize),
     SearchLibraryToHand(Vec<Card>),
     SearchLibraryToBattlefield(Vec<GameObject<Card>>),
-    Trigger(Trigger)
+    Trigger(Trigger),
+    PayMana(ManaPool),
+    AddMana(ManaPool),
+    SetTapState(GameObjectId, TapState),
+    ReturnToHand(GameObjectId),
+    Decide(Decision),
+    ResolveTop,
+    PutOntoBattlefield(GameObject<Card>),
+    PutIntoGraveyard(Card),
+    SetLandPlays(usize),
+    EmptyManaPool,
+    AdvanceStep,
+    Sacrifice(GameObjectId),
+    DelayTrigger(Trigger),
+    FireDelayedTriggers,
+    LoseGame(LossReason),
+    ShuffleIntoLibrary(Card),
+    AddLoreCounter(GameObjectId),
+    ReturnFromGraveyard(GameObject<Card>),
+    Discard(Card),
+    Destroy(GameObjectId),
+    SearchForBasicLand(PlayerId),
+    BecomeCopy {
+        id: GameObjectId,
+        copy_of: Option<Card>,
+        until_end_of_turn: bool,
+    },
+    SearchLibraryToTop(Vec<Card>),
+    SetHaste(GameObjectId, bool),
+    SetAttacking(GameObjectId, bool),
+    DealDamage(PlayerId, usize),
+    WinGame,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum GameAction {
     PassPriority,
     Primitive(PrimitiveGameAction),
-    CastSpell(Spell),
+    PlayLand(Land),
+    CastSpell {
+        spell: Spell,
+        x: u8,
+    },
     ActivateAbility {
-        source: GameObjectId,
+        source: AbilitySource,
         target: Option<Target>,
     },
+    ActivateManaAbility {
+        source: GameObjectId,
+        ability: ManaAbility,
+    },
+    DeclareAttackers(Vec<GameObjectId>),
+    Choose(Choice),
     Sequence(Vec<PrimitiveGameAction>),
 }
 
+/// A permanent put onto the battlefield and the number of triggers it put on the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entered {
+    pub id: GameObjectId,
+    pub triggers: usize,
+}
+
+/// A card taken out of the library by a search and where it was, or None if
+/// it wasn't in the library
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Found {
+    pub card: Card,
+    pub position: Option<LibraryPosition>,
+}
+
 pub enum PrimitiveGameActionResult {
-    DrawCards(Vec<Card>),
-    MillCards(Vec<Card>),
-    PlayLand(GameObjectId),
+    /// The cards taken off the library, of which the first `from_top` were
+    /// known top cards
+    DrawCards {
+        cards: Vec<Card>,
+        from_top: usize,
+    },
+    MillCards {
+        cards: Vec<Card>,
+        from_top: usize,
+    },
+    PlayLand {
+        hand_index: Option<usize>,
+        entered: Entered,
+    },
     IncreaseLandPlays(usize),
-    SearchLibraryToHand(Vec<Card>),
-    SearchLibraryToBattlefield(Vec<GameObjectId>),
+    SearchLibraryToHand(Vec<Found>),
+    SearchLibraryToBattlefield(Vec<(Found, Option<Entered>)>),
     Trigger,
+    PayMana(ManaPool),
+    AddMana(ManaPool),
+    SetTapState(GameObjectId, Option<TapState>),
+    ReturnToHand(GameObjectId, PlayerId, Option<GameObject<Card>>),
+    Decide,
+    ResolveTop {
+        object: Option<StackObject>,
+        effects: Vec<PrimitiveGameActionResult>,
+    },
+    PutOntoBattlefield(Option<Entered>),
+    PutIntoGraveyard(Card),
+    SetLandPlays(usize),
+    EmptyManaPool(ManaPool),
+    AdvanceStep {
+        turn: Turn,
+        effects: Vec<PrimitiveGameActionResult>,
+    },
+    Sacrifice(GameObjectId, Option<GameObject<Card>>),
+    DelayTrigger,
+    FireDelayedTriggers(Vec<Trigger>),
+    LoseGame(Option<GameOutcome>),
+    ShuffleIntoLibrary(Card),
+    AddLoreCounter(GameObjectId),
+    ReturnFromGraveyard {
+        graveyard_index: Option<usize>,
+        entered: Option<Entered>,
+    },
+    Discard(Card, Option<usize>),
+    Destroy(GameObjectId, PlayerId, Option<GameObject<Card>>),
+    SearchForBasicLand(PlayerId, Option<(LibraryPosition, GameObjectId)>),
+    BecomeCopy(GameObjectId, Option<(Option<Card>, bool)>),
+    SearchLibraryToTop(Vec<Found>),
+    SetHaste(GameObjectId, Option<bool>),
+    SetAttacking(GameObjectId, Option<bool>),
+    DealDamage(PlayerId, usize),
+    WinGame(Option<GameOutcome>),
 }
 
 pub enum GameActionResult {
-    PassPriority,
+    PassPriority {
+        priority: PlayerId,
+        priority_passes: usize,
+        effect: Option<PrimitiveGameActionResult>,
+    },
     Primitive(PrimitiveGameActionResult),
-    CastSpell,
-    ActivateAbility(GameObjectId),
+    PlayLand(Vec<PrimitiveGameActionResult>),
+    CastSpell {
+        priority_passes: usize,
+        /// Where the spell was in hand, or None if it couldn't be cast
+        hand_index: Option<usize>,
+        costs: Vec<PrimitiveGameActionResult>,
+    },
+    ActivateAbility {
+        source: AbilitySource,
+        ability: Option<Ability>,
+        priority_passes: usize,
+        costs: Vec<PrimitiveGameActionResult>,
+    },
+    ActivateManaAbility(Vec<PrimitiveGameActionResult>),
+    DeclareAttackers {
+        attackers_declared: bool,
+        results: Vec<PrimitiveGameActionResult>,
+    },
+    Choose {
+        decision: Option<Decision>,
+        effects: Vec<PrimitiveGameActionResult>,
+    },
     Sequence(Vec<PrimitiveGameActionResult>),
 }
 
@@ -48,30 +177,154 @@ impl GameAction {
     pub fn apply(&self, game_state: &mut GameState) -> GameActionResult {
         match self {
             GameAction::PassPriority => {
-                // Switch priority to the other player
-                game_state.priority = match game_state.priority {
-                    PlayerId::Active => PlayerId::NonActive,
-                    PlayerId::NonActive => PlayerId::Active,
-                };
-                GameActionResult::PassPriority
+                let priority = game_state.priority;
+                let priority_passes = game_state.priority_passes;
+                // A pending decision has to be answered first
+                if game_state.decision.is_some() {
+                    return GameActionResult::PassPriority { priority, priority_passes, effect: None };
+                }
+                game_state.priority_passes += 1;
+
+                let mut effect = None;
+                if game_state.priority_passes >= game_state.player_count() {
+                    // Everyone passed in succession: resolve the top of the stack, or
+                    // move to the next step if it's empty, and give priority back to
+                    // the active player
+                    game_state.priority_passes = 0;
+                    game_state.priority = PlayerId::Active;
+                    let action = if game_state.stack.objects.is_empty() {
+                        PrimitiveGameAction::AdvanceStep
+                    } else {
+                        PrimitiveGameAction::ResolveTop
+                    };
+                    effect = Some(action.apply(game_state));
+                } else {
+                    // Switch priority to the other player
+                    game_state.priority = match game_state.priority {
+                        PlayerId::Active => PlayerId::NonActive,
+                        PlayerId::NonActive => PlayerId::Active,
+                    };
+                }
+                GameActionResult::PassPriority { priority, priority_passes, effect }
             }
             GameAction::Primitive(primitive_action) => {
                 let result = primitive_action.apply(game_state);
                 GameActionResult::Primitive(result)
             }
-            GameAction::CastSpell(spell) => {
-                // Add spell to stack
-                game_state.stack.objects.push(StackObject::Spell(*spell));
-                GameActionResult::CastSpell
+            GameAction::PlayLand(land) => {
+                // Playing a land from hand uses up one of the turn's land plays
+                if !game_state.can_play_land(*land) {
+                    return GameActionResult::PlayLand(Vec::new());
+                }
+                let land_plays = game_state.active_player.battlefield.land_plays;
+                let actions = [
+                    PrimitiveGameAction::SetLandPlays(land_plays.saturating_sub(1)),
+                    PrimitiveGameAction::PlayLand(*land, TapState::Untapped),
+                ];
+                let results = actions.iter().map(|action| action.apply(game_state)).collect();
+                GameActionResult::PlayLand(results)
+            }
+            GameAction::CastSpell { spell, x } => {
+                // Move the spell from hand to the stack and pay for it with the
+                // chosen X; the caster keeps priority
+                let priority_passes = game_state.priority_passes;
+                if !game_state.can_cast(*spell, *x) {
+                    return GameActionResult::CastSpell {
+                        priority_passes,
+                        hand_index: None,
+                        costs: Vec::new(),
+                    };
+                }
+                let hand_index = game_state.active_player.hand.remove_card(Card::Spell(*spell));
+                let costs = PrimitiveGameAction::pay_mana(game_state, &spell.mana_value(), *x)
+                    .map(|payment| payment.apply(game_state))
+                    .into_iter()
+                    .collect();
+                game_state.stack.push(StackObject::Spell { spell: *spell, x: *x });
+                game_state.priority_passes = 0;
+                GameActionResult::CastSpell { priority_passes, hand_index, costs }
             }
             GameAction::ActivateAbility { source, target } => {
-                // Add activated ability to stack
-                let ability = StackObject::ActivatedAbility {
-                    source: *source,
-                    target: target.clone(),
+                // Pay the ability's costs, then add it to the stack; the controller
+                // keeps priority. Nothing happens if it can't be activated.
+                let priority_passes = game_state.priority_passes;
+                let activated = game_state
+                    .activated_ability(*source)
+                    .filter(|activated| game_state.can_activate(*source, activated, target.as_ref()));
+                let mut costs = Vec::new();
+                if let Some(activated) = &activated {
+                    let mut actions = Vec::new();
+                    if let Some(payment) = PrimitiveGameAction::pay_mana(game_state, &activated.cost, 0) {
+                        actions.push(payment);
+                    }
+                    match *source {
+                        AbilitySource::Battlefield(id) => {
+                            if activated.tap {
+                                actions.push(PrimitiveGameAction::SetTapState(id, TapState::Tapped));
+                            }
+                            if activated.sacrifice {
+                                actions.push(PrimitiveGameAction::Sacrifice(id));
+                            }
+                        }
+                        AbilitySource::Hand(card) => {
+                            if activated.discard {
+                                actions.push(PrimitiveGameAction::Discard(card));
+                            }
+                        }
+                    }
+                    costs = actions.iter().map(|action| action.apply(game_state)).collect();
+                    game_state.stack.push(StackObject::ActivatedAbility {
+                        source: *source,
+                        ability: activated.ability,
+                        target: target.clone(),
+                    });
+                    game_state.priority_passes = 0;
+                }
+                let ability = activated.map(|activated| activated.ability);
+                GameActionResult::ActivateAbility { source: *source, ability, priority_passes, costs }
+            }
+            GameAction::ActivateManaAbility { source, ability } => {
+                // Mana abilities don't use the stack: tap, pay any extra cost, add the mana
+                if !game_state.available_mana_abilities(*source).contains(ability) {
+                    return GameActionResult::ActivateManaAbility(Vec::new());
+                }
+                let mut actions = vec![PrimitiveGameAction::SetTapState(*source, TapState::Tapped)];
+                if let Some(payment) = PrimitiveGameAction::pay_mana(game_state, &ability.cost, 0) {
+                    actions.push(payment);
+                }
+                actions.push(PrimitiveGameAction::AddMana(ability.produces.clone()));
+                let results = actions.iter().map(|action| action.apply(game_state)).collect();
+                GameActionResult::ActivateManaAbility(results)
+            }
+            GameAction::DeclareAttackers(attackers) => {
+                // Attacking creatures tap and put their attack triggers on the stack
+                let attackers_declared = game_state.turn.attackers_declared;
+                let mut results = Vec::new();
+                if !game_state.can_declare_attackers() {
+                    return GameActionResult::DeclareAttackers { attackers_declared, results };
+                }
+                for &id in attackers {
+                    if !game_state.active_player.battlefield.can_attack(id, &game_state.turn) {
+                        continue;
+                    }
+                    results.push(PrimitiveGameAction::SetTapState(id, TapState::Tapped).apply(game_state));
+                    results.push(PrimitiveGameAction::SetAttacking(id, true).apply(game_state));
+                    for trigger in game_state.attack_triggers(id) {
+                        results.push(PrimitiveGameAction::Trigger(trigger).apply(game_state));
+                    }
+                }
+                game_state.turn.attackers_declared = true;
+                GameActionResult::DeclareAttackers { attackers_declared, results }
+            }
+            GameAction::Choose(choice) => {
+                // Answer the pending decision and apply its effects
+                let decision = game_state.decision.take();
+                let actions = match &decision {
+                    Some(decision) => decision.resolve(choice, game_state),
+                    None => Vec::new(),
                 };
-                game_state.stack.objects.push(ability);
-                GameActionResult::ActivateAbility(*source)
+                let effects = actions.iter().map(|action| action.apply(game_state)).collect();
+                GameActionResult::Choose { decision, effects }
             }
             GameAction::Sequence(actions) => {
                 let mut results = Vec::new();
@@ -86,104 +339,263 @@ impl GameAction {
 }
 
 impl PrimitiveGameAction {
+    /// Builds the payment for `cost` out of the active player's mana pool,
+    /// returning None if the pool cannot cover it
+    pub fn pay_mana(game_state: &GameState, cost: &ManaValue, x: u8) -> Option<Self> {
+        game_state
+            .active_player
+            .mana_pool
+            .payment(cost, x)
+            .map(PrimitiveGameAction::PayMana)
+    }
+
     pub fn apply(&self, game_state: &mut GameState) -> PrimitiveGameActionResult {
         match self {
             PrimitiveGameAction::DrawCards(count) => {
                 let mut drawn_cards = Vec::new();
+                let from_top = game_state.active_player.library.top.len().min(*count);
                 for _ in 0..*count {
-                    if let Some(card) = game_state.active_player.library.draw_random_card() {
-                        match card {
-                            Card::Land(land) => game_state.active_player.hand.lands.push(land),
-                            Card::Spell(spell) => game_state.active_player.hand.spells.push(spell),
-                        }
+                    if let Some(card) = game_state.active_player.library.draw_card() {
+                        game_state.active_player.hand.add_card(card);
                         drawn_cards.push(card);
                     }
                 }
-                PrimitiveGameActionResult::DrawCards(drawn_cards)
+                PrimitiveGameActionResult::DrawCards { cards: drawn_cards, from_top }
             }
             PrimitiveGameAction::MillCards(count) => {
                 let mut milled_cards = Vec::new();
+                let from_top = game_state.active_player.library.top.len().min(*count);
                 for _ in 0..*count {
-                    if let Some(card) = game_state.active_player.library.draw_random_card() {
-                        match card {
-                            Card::Land(land) => game_state.active_player.graveyard.lands.push(land),
-                            Card::Spell(spell) => game_state.active_player.graveyard.spells.push(spell),
-                        }
+                    if let Some(card) = game_state.active_player.library.draw_card() {
+                        game_state.active_player.graveyard.add_card(card);
                         milled_cards.push(card);
                     }
                 }
-                PrimitiveGameActionResult::MillCards(milled_cards)
+                PrimitiveGameActionResult::MillCards { cards: milled_cards, from_top }
             }
             PrimitiveGameAction::PlayLand(land, tap_state) => {
-                let id = game_state.next_game_object_id();
-                let game_object = GameObject {
-                    permanent: *land,
-                    tap_state: *tap_state,
-                };
-                game_state.active_player.battlefield.lands.insert(id, game_object);
-                PrimitiveGameActionResult::PlayLand(id)
+                let hand_index = game_state.active_player.hand.remove_land(*land);
+                let entered = enter_battlefield(game_state, GameObject::new(Card::Land(*land), *tap_state))
+                    .expect("lands can always enter the battlefield");
+                PrimitiveGameActionResult::PlayLand { hand_index, entered }
             }
             PrimitiveGameAction::IncreaseLandPlays(amount) => {
                 game_state.active_player.battlefield.land_plays += amount;
                 PrimitiveGameActionResult::IncreaseLandPlays(*amount)
             }
             PrimitiveGameAction::SearchLibraryToHand(cards) => {
-                for card in cards {
+                let mut found = Vec::new();
+                for &card in cards {
                     // Remove card from library
-                    game_state.active_player.library.cards[*card] =
-                        game_state.active_player.library.cards[*card].saturating_sub(1);
-                    game_state.active_player.library.size =
-                        game_state.active_player.library.size.saturating_sub(1);
+                    let position = game_state.active_player.library.remove_card(card);
 
                     // Add to hand
-                    match card {
-                        Card::Land(land) => game_state.active_player.hand.lands.push(*land),
-                        Card::Spell(spell) => game_state.active_player.hand.spells.push(*spell),
-                    }
+                    game_state.active_player.hand.add_card(card);
+                    found.push(Found { card, position });
                 }
-                PrimitiveGameActionResult::SearchLibraryToHand(cards.clone())
+                PrimitiveGameActionResult::SearchLibraryToHand(found)
             }
             PrimitiveGameAction::SearchLibraryToBattlefield(game_objects) => {
-                let mut object_ids = Vec::new();
+                let mut found = Vec::new();
                 for game_object in game_objects {
                     // Remove card from library
-                    game_state.active_player.library.cards[game_object.permanent] =
-                        game_state.active_player.library.cards[game_object.permanent].saturating_sub(1);
-                    game_state.active_player.library.size =
-                        game_state.active_player.library.size.saturating_sub(1);
+                    let card = game_object.permanent;
+                    let position = game_state.active_player.library.remove_card(card);
 
                     // Add to battlefield
-                    match game_object.permanent {
-                        Card::Land(land) => {
-                            let id = game_state.next_game_object_id();
-                            let battlefield_object = GameObject {
-                                permanent: land,
-                                tap_state: game_object.tap_state,
-                            };
-                            game_state.active_player.battlefield.lands.insert(id, battlefield_object);
-                            object_ids.push(id);
-                        }
-                        Card::Spell(Spell::Permanent(permanent)) => {
-                            let id = game_state.next_game_object_id();
-                            let battlefield_object = GameObject {
-                                permanent: permanent,
-                                tap_state: game_object.tap_state,
-                            };
-                            game_state.active_player.battlefield.non_lands.insert(id, battlefield_object);
-                            object_ids.push(id);
-                        }
-                        Card::Spell(Spell::NonPermanent(_)) => {
-                            // Non-permanent spells can't enter the battlefield
-                            // This should probably be an error, but for now we'll skip it
-                        }
-                    }
+                    // Non-permanent spells can't enter the battlefield
+                    // This should probably be an error, but for now we'll skip it
+                    let entered = enter_battlefield(game_state, game_object.clone());
+                    found.push((Found { card, position }, entered));
                 }
-                PrimitiveGameActionResult::SearchLibraryToBattlefield(object_ids)
+                PrimitiveGameActionResult::SearchLibraryToBattlefield(found)
             }
             PrimitiveGameAction::Trigger(trigger) => {
-                game_state.stack.objects.push(StackObject::Trigger(trigger.clone()));
+                game_state.stack.push(StackObject::Trigger(trigger.clone()));
                 PrimitiveGameActionResult::Trigger
             }
+            PrimitiveGameAction::PayMana(payment) => {
+                // Deduct the chosen allocation from the mana pool
+                game_state.active_player.mana_pool.remove(payment);
+                PrimitiveGameActionResult::PayMana(payment.clone())
+            }
+            PrimitiveGameAction::AddMana(mana) => {
+                game_state.active_player.mana_pool.add(mana);
+                PrimitiveGameActionResult::AddMana(mana.clone())
+            }
+            PrimitiveGameAction::SetTapState(id, tap_state) => {
+                let previous = game_state.active_player.battlefield.set_tap_state(*id, *tap_state);
+                PrimitiveGameActionResult::SetTapState(*id, previous)
+            }
+            PrimitiveGameAction::ReturnToHand(id) => {
+                // Tokens cease to exist instead of going to hand
+                let controller = game_state.controller(*id).unwrap_or(PlayerId::Active);
+                let mut object = None;
+                if let Some(player) = game_state.player_mut(controller) {
+                    object = player.battlefield.remove(*id);
+                    if let Some(object) = object.as_ref().filter(|object| !object.token) {
+                        player.hand.add_card(object.permanent);
+                    }
+                }
+                PrimitiveGameActionResult::ReturnToHand(*id, controller, object)
+            }
+            PrimitiveGameAction::Decide(decision) => {
+                game_state.decision = Some(decision.clone());
+                PrimitiveGameActionResult::Decide
+            }
+            PrimitiveGameAction::ResolveTop => {
+                // Take the object off the stack, then apply its effects
+                let object = game_state.stack.pop();
+                let mut effects = Vec::new();
+                if let Some(object) = &object {
+                    for action in object.resolve(game_state) {
+                        effects.push(action.apply(game_state));
+                    }
+                }
+                PrimitiveGameActionResult::ResolveTop { object, effects }
+            }
+            PrimitiveGameAction::PutOntoBattlefield(game_object) => {
+                let entered = enter_battlefield(game_state, game_object.clone());
+                PrimitiveGameActionResult::PutOntoBattlefield(entered)
+            }
+            PrimitiveGameAction::PutIntoGraveyard(card) => {
+                game_state.active_player.graveyard.add_card(*card);
+                PrimitiveGameActionResult::PutIntoGraveyard(*card)
+            }
+            PrimitiveGameAction::SetLandPlays(land_plays) => {
+                let previous = std::mem::replace(&mut game_state.active_player.battlefield.land_plays, *land_plays);
+                PrimitiveGameActionResult::SetLandPlays(previous)
+            }
+            PrimitiveGameAction::EmptyManaPool => {
+                let pool = std::mem::take(&mut game_state.active_player.mana_pool);
+                PrimitiveGameActionResult::EmptyManaPool(pool)
+            }
+            PrimitiveGameAction::AdvanceStep => {
+                // Mana empties between steps, then run the turn-based actions of each
+                // step we enter until one where players get priority
+                let turn = game_state.turn;
+                let mut effects = vec![PrimitiveGameAction::EmptyManaPool.apply(game_state)];
+                loop {
+                    game_state.turn.step = game_state.turn.step.next();
+                    if game_state.turn.step == Step::Untap {
+                        game_state.turn.number += 1;
+                        game_state.turn.attackers_declared = false;
+                    }
+                    for action in turn_based_actions(game_state) {
+                        effects.push(action.apply(game_state));
+                    }
+                    if game_state.turn.step.has_priority() {
+                        break;
+                    }
+                }
+                PrimitiveGameActionResult::AdvanceStep { turn, effects }
+            }
+            PrimitiveGameAction::Sacrifice(id) => {
+                // Tokens cease to exist instead of going to the graveyard
+                let object = game_state.active_player.battlefield.remove(*id);
+                if let Some(object) = object.as_ref().filter(|object| !object.token) {
+                    game_state.active_player.graveyard.add_card(object.permanent);
+                }
+                PrimitiveGameActionResult::Sacrifice(*id, object)
+            }
+            PrimitiveGameAction::DelayTrigger(trigger) => {
+                game_state.delayed_triggers.push(trigger.clone());
+                PrimitiveGameActionResult::DelayTrigger
+            }
+            PrimitiveGameAction::FireDelayedTriggers => {
+                let triggers = std::mem::take(&mut game_state.delayed_triggers);
+                game_state.stack.extend(triggers.iter().cloned().map(StackObject::Trigger));
+                PrimitiveGameActionResult::FireDelayedTriggers(triggers)
+            }
+            PrimitiveGameAction::LoseGame(reason) => {
+                let previous = game_state.outcome.replace(GameOutcome::Lost(*reason));
+                PrimitiveGameActionResult::LoseGame(previous)
+            }
+            PrimitiveGameAction::ShuffleIntoLibrary(card) => {
+                game_state.active_player.library.add_card(*card);
+                PrimitiveGameActionResult::ShuffleIntoLibrary(*card)
+            }
+            PrimitiveGameAction::AddLoreCounter(id) => {
+                let battlefield = &mut game_state.active_player.battlefield;
+                if let Some(lore_counters) = battlefield.lands.get(id).map(|land| land.lore_counters) {
+                    battlefield.set_lore_counters(*id, lore_counters + 1);
+                }
+                PrimitiveGameActionResult::AddLoreCounter(*id)
+            }
+            PrimitiveGameAction::Discard(card) => {
+                let hand_index = game_state.active_player.hand.remove_card(*card);
+                if hand_index.is_some() {
+                    game_state.active_player.graveyard.add_card(*card);
+                }
+                PrimitiveGameActionResult::Discard(*card, hand_index)
+            }
+            PrimitiveGameAction::Destroy(id) => {
+                // Tokens cease to exist instead of going to the graveyard
+                let controller = game_state.controller(*id).unwrap_or(PlayerId::Active);
+                let mut object = None;
+                if let Some(player) = game_state.player_mut(controller) {
+                    object = player.battlefield.remove(*id);
+                    if let Some(object) = object.as_ref().filter(|object| !object.token) {
+                        player.graveyard.add_card(object.permanent);
+                    }
+                }
+                PrimitiveGameActionResult::Destroy(*id, controller, object)
+            }
+            PrimitiveGameAction::SearchForBasicLand(player_id) => {
+                // The only land with a basic land type the engine knows is Forest
+                let land = Card::Land(Land::Forest);
+                let position = game_state
+                    .player_mut(*player_id)
+                    .and_then(|player| player.library.remove_card(land));
+                let found = position.map(|position| {
+                    let id = game_state.next_game_object_id();
+                    if let Some(player) = game_state.player_mut(*player_id) {
+                        player.battlefield.insert(id, GameObject::new(land, TapState::Untapped));
+                    }
+                    (position, id)
+                });
+                PrimitiveGameActionResult::SearchForBasicLand(*player_id, found)
+            }
+            PrimitiveGameAction::BecomeCopy { id, copy_of, until_end_of_turn } => {
+                let previous = game_state
+                    .active_player
+                    .battlefield
+                    .set_copy_of(*id, *copy_of, *until_end_of_turn);
+                PrimitiveGameActionResult::BecomeCopy(*id, previous)
+            }
+            PrimitiveGameAction::SearchLibraryToTop(cards) => {
+                let mut found = Vec::new();
+                for &card in cards {
+                    let library = &mut game_state.active_player.library;
+                    let position = library.remove_card(card);
+                    library.put_on_top(card);
+                    found.push(Found { card, position });
+                }
+                PrimitiveGameActionResult::SearchLibraryToTop(found)
+            }
+            PrimitiveGameAction::SetHaste(id, haste) => {
+                let previous = game_state.active_player.battlefield.set_haste(*id, *haste);
+                PrimitiveGameActionResult::SetHaste(*id, previous)
+            }
+            PrimitiveGameAction::SetAttacking(id, attacking) => {
+                let previous = game_state.active_player.battlefield.set_attacking(*id, *attacking);
+                PrimitiveGameActionResult::SetAttacking(*id, previous)
+            }
+            PrimitiveGameAction::DealDamage(player_id, amount) => {
+                if let Some(player) = game_state.player_mut(*player_id) {
+                    player.life_total -= *amount as isize;
+                }
+                PrimitiveGameActionResult::DealDamage(*player_id, *amount)
+            }
+            PrimitiveGameAction::WinGame => {
+                let previous = game_state.outcome.replace(GameOutcome::Won);
+                PrimitiveGameActionResult::WinGame(previous)
+            }
+            PrimitiveGameAction::ReturnFromGraveyard(game_object) => {
+                let graveyard_index = game_state.active_player.graveyard.remove_card(game_object.permanent);
+                let entered = graveyard_index.and_then(|_| enter_battlefield(game_state, game_object.clone()));
+                PrimitiveGameActionResult::ReturnFromGraveyard { graveyard_index, entered }
+            }
         }
     }
 }
@@ -191,84 +603,212 @@ impl PrimitiveGameAction {
 impl PrimitiveGameActionResult {
     pub fn revert(&self, game_state: &mut GameState) {
         match self {
-            PrimitiveGameActionResult::DrawCards(cards) => {
-                // Count how many lands and spells were drawn
-                let mut lands_drawn = 0;
-                let mut spells_drawn = 0;
-                for card in cards {
-                    match card {
-                        Card::Land(_) => lands_drawn += 1,
-                        Card::Spell(_) => spells_drawn += 1,
-                    }
-                    game_state.active_player.library.add_card(*card);
+            PrimitiveGameActionResult::DrawCards { cards, from_top } => {
+                // Remove the drawn cards from hand and put them back in the library
+                for card in cards.iter().rev() {
+                    game_state.active_player.hand.remove_last(*card);
                 }
-
-                // Truncate the hand vectors to remove the drawn cards
-                let lands_len = game_state.active_player.hand.lands.len();
-                let spells_len = game_state.active_player.hand.spells.len();
-
-                game_state.active_player.hand.lands.truncate(lands_len.saturating_sub(lands_drawn));
-                game_state.active_player.hand.spells.truncate(spells_len.saturating_sub(spells_drawn));
-
+                game_state.active_player.library.undraw(cards, *from_top);
             }
-            PrimitiveGameActionResult::MillCards(cards) => {
+            PrimitiveGameActionResult::MillCards { cards, from_top } => {
                 // Remove cards from graveyard and add them back to library
                 for card in cards.iter().rev() {
-                    match card {
-                        Card::Land(land) => {
-                            if let Some(pos) = game_state.active_player.graveyard.lands.iter().position(|&l| l == *land) {
-                                game_state.active_player.graveyard.lands.remove(pos);
-                            }
-                        }
-                        Card::Spell(spell) => {
-                            if let Some(pos) = game_state.active_player.graveyard.spells.iter().position(|&s| s == *spell) {
-                                game_state.active_player.graveyard.spells.remove(pos);
-                            }
-                        }
-                    }
-                    game_state.active_player.library.add_card(*card);
+                    game_state.active_player.graveyard.remove_last(*card);
                 }
+                game_state.active_player.library.undraw(cards, *from_top);
             }
-            PrimitiveGameActionResult::PlayLand(id) => {
-                // Remove land from battlefield
-                game_state.active_player.battlefield.lands.remove(id);
+            PrimitiveGameActionResult::PlayLand { hand_index, entered } => {
+                // Remove land from battlefield and put it back where it was in hand
+                if let Some(card) = leave_battlefield(game_state, entered) {
+                    if let Some(index) = hand_index {
+                        game_state.active_player.hand.insert_card(*index, card);
+                    }
+                }
             }
             PrimitiveGameActionResult::IncreaseLandPlays(amount) => {
                 // Decrease land plays
                 game_state.active_player.battlefield.land_plays =
                     game_state.active_player.battlefield.land_plays.saturating_sub(*amount);
             }
-            PrimitiveGameActionResult::SearchLibraryToHand(cards) => {
-                // Remove cards from hand and add them back to library
-                for card in cards.iter().rev() {
-                    match card {
-                        Card::Land(land) => {
-                            if let Some(pos) = game_state.active_player.hand.lands.iter().position(|&l| l == *land) {
-                                game_state.active_player.hand.lands.remove(pos);
-                            }
-                        }
-                        Card::Spell(spell) => {
-                            if let Some(pos) = game_state.active_player.hand.spells.iter().position(|&s| s == *spell) {
-                                game_state.active_player.hand.spells.remove(pos);
-                            }
-                        }
+            PrimitiveGameActionResult::SearchLibraryToHand(found) => {
+                // Remove cards from hand and put them back where they were in the library
+                for found in found.iter().rev() {
+                    game_state.active_player.hand.remove_last(found.card);
+                    if let Some(position) = found.position {
+                        game_state.active_player.library.insert_card(found.card, position);
                     }
-                    game_state.active_player.library.add_card(*card);
                 }
             }
-            PrimitiveGameActionResult::SearchLibraryToBattlefield(object_ids) => {
-                // Remove objects from battlefield and add corresponding cards back to library
-                for id in object_ids.iter().rev() {
-                    if let Some(land_object) = game_state.active_player.battlefield.lands.remove(id) {
-                        game_state.active_player.library.add_card(Card::Land(land_object.permanent));
-                    } else if let Some(permanent_object) = game_state.active_player.battlefield.non_lands.remove(id) {
-                        game_state.active_player.library.add_card(Card::Spell(Spell::Permanent(permanent_object.permanent)));
+            PrimitiveGameActionResult::SearchLibraryToBattlefield(found) => {
+                // Remove objects from battlefield and put the cards back where they were in the library
+                for (found, entered) in found.iter().rev() {
+                    if let Some(entered) = entered {
+                        leave_battlefield(game_state, entered);
+                    }
+                    if let Some(position) = found.position {
+                        game_state.active_player.library.insert_card(found.card, position);
                     }
                 }
             }
             PrimitiveGameActionResult::Trigger => {
                 // Remove the last trigger from the stack
-                game_state.stack.objects.pop();
+                game_state.stack.pop();
+            }
+            PrimitiveGameActionResult::PayMana(payment) => {
+                // Refund the spent mana
+                game_state.active_player.mana_pool.add(payment);
+            }
+            PrimitiveGameActionResult::AddMana(mana) => {
+                // Remove the added mana
+                game_state.active_player.mana_pool.remove(mana);
+            }
+            PrimitiveGameActionResult::SetTapState(id, previous) => {
+                // Restore the previous tap state, if the object existed
+                if let Some(previous) = previous {
+                    game_state.active_player.battlefield.set_tap_state(*id, *previous);
+                }
+            }
+            PrimitiveGameActionResult::ReturnToHand(id, controller, object) => {
+                // Take the card back out of hand and restore the permanent
+                if let (Some(object), Some(player)) = (object, game_state.player_mut(*controller)) {
+                    if !object.token {
+                        player.hand.remove_last(object.permanent);
+                    }
+                    player.battlefield.insert(*id, object.clone());
+                }
+            }
+            PrimitiveGameActionResult::Decide => {
+                game_state.decision = None;
+            }
+            PrimitiveGameActionResult::ResolveTop { object, effects } => {
+                // Undo the effects, then put the object back on the stack
+                for effect in effects.iter().rev() {
+                    effect.revert(game_state);
+                }
+                if let Some(object) = object {
+                    game_state.stack.push(object.clone());
+                }
+            }
+            PrimitiveGameActionResult::PutOntoBattlefield(entered) => {
+                if let Some(entered) = entered {
+                    leave_battlefield(game_state, entered);
+                }
+            }
+            PrimitiveGameActionResult::PutIntoGraveyard(card) => {
+                game_state.active_player.graveyard.remove_last(*card);
+            }
+            PrimitiveGameActionResult::SetLandPlays(previous) => {
+                game_state.active_player.battlefield.land_plays = *previous;
+            }
+            PrimitiveGameActionResult::EmptyManaPool(pool) => {
+                game_state.active_player.mana_pool = pool.clone();
+            }
+            PrimitiveGameActionResult::AdvanceStep { turn, effects } => {
+                // Undo the turn-based actions, then go back to the previous step
+                for effect in effects.iter().rev() {
+                    effect.revert(game_state);
+                }
+                game_state.turn = *turn;
+            }
+            PrimitiveGameActionResult::Sacrifice(id, object) => {
+                // Take the card back out of the graveyard and restore the permanent
+                if let Some(object) = object {
+                    if !object.token {
+                        game_state.active_player.graveyard.remove_last(object.permanent);
+                    }
+                    game_state.active_player.battlefield.insert(*id, object.clone());
+                }
+            }
+            PrimitiveGameActionResult::DelayTrigger => {
+                game_state.delayed_triggers.pop();
+            }
+            PrimitiveGameActionResult::FireDelayedTriggers(triggers) => {
+                // Take the triggers back off the stack and wait for the next upkeep again
+                let len = game_state.stack.objects.len();
+                game_state.stack.truncate(len.saturating_sub(triggers.len()));
+                game_state.delayed_triggers = triggers.clone();
+            }
+            PrimitiveGameActionResult::LoseGame(previous) => {
+                game_state.outcome = *previous;
+            }
+            PrimitiveGameActionResult::ShuffleIntoLibrary(card) => {
+                game_state.active_player.library.remove_card(*card);
+            }
+            PrimitiveGameActionResult::AddLoreCounter(id) => {
+                let battlefield = &mut game_state.active_player.battlefield;
+                if let Some(lore_counters) = battlefield.lands.get(id).map(|land| land.lore_counters) {
+                    battlefield.set_lore_counters(*id, lore_counters.saturating_sub(1));
+                }
+            }
+            PrimitiveGameActionResult::Discard(card, hand_index) => {
+                if let Some(index) = hand_index {
+                    game_state.active_player.graveyard.remove_last(*card);
+                    game_state.active_player.hand.insert_card(*index, *card);
+                }
+            }
+            PrimitiveGameActionResult::Destroy(id, controller, object) => {
+                // Take the card back out of the graveyard and restore the permanent
+                if let (Some(object), Some(player)) = (object, game_state.player_mut(*controller)) {
+                    if !object.token {
+                        player.graveyard.remove_last(object.permanent);
+                    }
+                    player.battlefield.insert(*id, object.clone());
+                }
+            }
+            PrimitiveGameActionResult::SearchForBasicLand(player_id, id) => {
+                // Put the found land back where it was in the library
+                if let Some((position, id)) = id {
+                    if let Some(player) = game_state.player_mut(*player_id) {
+                        if let Some(object) = player.battlefield.remove(*id) {
+                            player.library.insert_card(object.permanent, *position);
+                        }
+                    }
+                    game_state.release_game_object_id(*id);
+                }
+            }
+            PrimitiveGameActionResult::BecomeCopy(id, previous) => {
+                // Restore what the object was copying, if it existed
+                if let Some((copy_of, until_end_of_turn)) = previous {
+                    game_state
+                        .active_player
+                        .battlefield
+                        .set_copy_of(*id, *copy_of, *until_end_of_turn);
+                }
+            }
+            PrimitiveGameActionResult::SearchLibraryToTop(found) => {
+                // Take the cards back off the top and put them back where they were
+                for found in found.iter().rev() {
+                    let library = &mut game_state.active_player.library;
+                    library.take_top();
+                    if let Some(position) = found.position {
+                        library.insert_card(found.card, position);
+                    }
+                }
+            }
+            PrimitiveGameActionResult::SetHaste(id, previous) => {
+                if let Some(previous) = previous {
+                    game_state.active_player.battlefield.set_haste(*id, *previous);
+                }
+            }
+            PrimitiveGameActionResult::SetAttacking(id, previous) => {
+                if let Some(previous) = previous {
+                    game_state.active_player.battlefield.set_attacking(*id, *previous);
+                }
+            }
+            PrimitiveGameActionResult::DealDamage(player_id, amount) => {
+                if let Some(player) = game_state.player_mut(*player_id) {
+                    player.life_total += *amount as isize;
+                }
+            }
+            PrimitiveGameActionResult::WinGame(previous) => {
+                game_state.outcome = *previous;
+            }
+            PrimitiveGameActionResult::ReturnFromGraveyard { graveyard_index, entered } => {
+                // Put the card back where it was in the graveyard
+                let card = entered.as_ref().and_then(|entered| leave_battlefield(game_state, entered));
+                if let (Some(card), Some(index)) = (card, graveyard_index) {
+                    game_state.active_player.graveyard.insert_card(*index, card);
+                }
             }
         }
     }
@@ -277,27 +817,58 @@ impl PrimitiveGameActionResult {
 impl GameActionResult {
     pub fn revert(&self, game_state: &mut GameState) {
         match self {
-            GameActionResult::PassPriority => {
-                // Switch priority back to the previous player
-                game_state.priority = match game_state.priority {
-                    PlayerId::Active => PlayerId::NonActive,
-                    PlayerId::NonActive => PlayerId::Active,
-                };
+            GameActionResult::PassPriority { priority, priority_passes, effect } => {
+                // Undo any resolution or step change, then give priority back to the
+                // previous player
+                if let Some(effect) = effect {
+                    effect.revert(game_state);
+                }
+                game_state.priority = *priority;
+                game_state.priority_passes = *priority_passes;
             }
             GameActionResult::Primitive(primitive_result) => {
                 primitive_result.revert(game_state);
             }
-            GameActionResult::CastSpell => {
-                // Remove the last spell from the stack
-                if let Some(StackObject::Spell(_)) = game_state.stack.objects.last() {
-                    game_state.stack.objects.pop();
+            GameActionResult::CastSpell { priority_passes, hand_index, costs } => {
+                // Take the spell off the stack, refund its costs and put it back
+                // where it was in hand
+                if let Some(index) = hand_index {
+                    if let Some(StackObject::Spell { spell, .. }) = game_state.stack.pop() {
+                        game_state.active_player.hand.insert_card(*index, Card::Spell(spell));
+                    }
+                }
+                for cost in costs.iter().rev() {
+                    cost.revert(game_state);
+                }
+                game_state.priority_passes = *priority_passes;
+            }
+            GameActionResult::ActivateAbility { ability, priority_passes, costs, .. } => {
+                // Remove the activated ability from the stack and refund its costs
+                if ability.is_some() {
+                    game_state.stack.pop();
+                }
+                for cost in costs.iter().rev() {
+                    cost.revert(game_state);
+                }
+                game_state.priority_passes = *priority_passes;
+            }
+            GameActionResult::PlayLand(results) | GameActionResult::ActivateManaAbility(results) => {
+                for result in results.iter().rev() {
+                    result.revert(game_state);
+                }
+            }
+            GameActionResult::DeclareAttackers { attackers_declared, results } => {
+                for result in results.iter().rev() {
+                    result.revert(game_state);
                 }
+                game_state.turn.attackers_declared = *attackers_declared;
             }
-            GameActionResult::ActivateAbility(_source) => {
-                // Remove the last activated ability from the stack
-                if let Some(StackObject::ActivatedAbility { .. }) = game_state.stack.objects.last() {
-                    game_state.stack.objects.pop();
+            GameActionResult::Choose { decision, effects } => {
+                // Undo the effects and ask the decision again
+                for effect in effects.iter().rev() {
+                    effect.revert(game_state);
                 }
+                game_state.decision = decision.clone();
             }
             GameActionResult::Sequence(results) => {
                 // Revert actions in reverse order
@@ -307,4 +878,277 @@ impl GameActionResult {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Returns the turn-based actions performed as the game enters the current step
+fn turn_based_actions(game_state: &GameState) -> Vec<PrimitiveGameAction> {
+    match game_state.turn.step {
+        Step::Untap => {
+            let mut actions: Vec<PrimitiveGameAction> = game_state
+                .active_player
+                .battlefield
+                .tapped()
+                .into_iter()
+                .map(|id| PrimitiveGameAction::SetTapState(id, TapState::Untapped))
+                .collect();
+            actions.push(PrimitiveGameAction::SetLandPlays(1));
+            actions
+        }
+        Step::Upkeep if !game_state.delayed_triggers.is_empty() => {
+            vec![PrimitiveGameAction::FireDelayedTriggers]
+        }
+        Step::Draw if !game_state.turn.skips_draw() => vec![PrimitiveGameAction::DrawCards(1)],
+        Step::PrecombatMain => {
+            // Each Saga gets a lore counter and triggers its next chapter
+            let mut actions = Vec::new();
+            for id in game_state.sagas() {
+                actions.push(PrimitiveGameAction::AddLoreCounter(id));
+                let lore_counters = game_state.active_player.battlefield.lands[&id].lore_counters + 1;
+                if let Some(trigger) = game_state.chapter_trigger(id, lore_counters) {
+                    actions.push(PrimitiveGameAction::Trigger(trigger));
+                }
+            }
+            actions
+        }
+        Step::PostcombatMain => {
+            // Attacking creatures deal their combat damage to the opponent as
+            // combat ends, and stop attacking
+            let attackers = game_state.active_player.battlefield.attacking();
+            let damage: usize = attackers.iter().map(|&id| game_state.power(id)).sum();
+            let mut actions = Vec::new();
+            if damage > 0 {
+                actions.push(PrimitiveGameAction::DealDamage(PlayerId::NonActive, damage));
+                let lethal = game_state
+                    .non_active_player
+                    .as_ref()
+                    .is_some_and(|opponent| opponent.life_total <= damage as isize);
+                if lethal {
+                    actions.push(PrimitiveGameAction::WinGame);
+                }
+            }
+            actions.extend(attackers.into_iter().map(|id| PrimitiveGameAction::SetAttacking(id, false)));
+            actions
+        }
+        Step::Cleanup => {
+            // Effects that last until end of turn wear off
+            let battlefield = &game_state.active_player.battlefield;
+            let mut actions = Vec::new();
+            for id in battlefield.until_end_of_turn() {
+                let Some(object) = battlefield.get(id) else {
+                    continue;
+                };
+                if object.haste {
+                    actions.push(PrimitiveGameAction::SetHaste(id, false));
+                }
+                if object.copy_until_end_of_turn {
+                    actions.push(PrimitiveGameAction::BecomeCopy {
+                        id,
+                        copy_of: None,
+                        until_end_of_turn: false,
+                    });
+                }
+            }
+            actions
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Puts a card onto the active player's battlefield, applying its entering
+/// rules and putting the triggers it causes on the stack. Returns None for
+/// cards that can't be permanents.
+fn enter_battlefield(game_state: &mut GameState, game_object: GameObject<Card>) -> Option<Entered> {
+    let id = game_state.next_game_object_id();
+    let game_object = game_state.entering_object(game_object);
+    if !game_state.active_player.battlefield.insert(id, game_object) {
+        return None;
+    }
+    let triggers = game_state.enter_triggers(id);
+    let count = triggers.len();
+    game_state.stack.extend(triggers.into_iter().map(StackObject::Trigger));
+    Some(Entered { id, triggers: count })
+}
+
+/// Reverts enter_battlefield, returning the card that left
+fn leave_battlefield(game_state: &mut GameState, entered: &Entered) -> Option<Card> {
+    let len = game_state.stack.objects.len();
+    game_state.stack.truncate(len.saturating_sub(entered.triggers));
+    game_state.release_game_object_id(entered.id);
+    game_state
+        .active_player
+        .battlefield
+        .remove(entered.id)
+        .map(|object| object.permanent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mana::ManaType;
+
+    #[test]
+    fn passing_in_succession_resolves_the_top_of_the_stack() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let amulet = Spell::Permanent(Permanent::AmuletOfVigor);
+        game_state.active_player.hand.spells.push(amulet);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Colorless, 1);
+        game_state.non_active_player = Some(game_state.active_player.clone());
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::CastSpell { spell: amulet, x: 0 }.apply(&mut game_state)];
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        assert_eq!(game_state.priority, PlayerId::NonActive);
+        assert_eq!(game_state.stack.objects, vec![StackObject::Spell { spell: amulet, x: 0 }]);
+
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        assert_eq!(game_state.priority, PlayerId::Active);
+        assert!(game_state.stack.objects.is_empty());
+        assert_eq!(game_state.amulets().len(), 1);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn resolved_instants_go_to_the_graveyard() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let pact = Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact));
+        game_state.active_player.hand.spells.push(pact);
+        GameAction::CastSpell { spell: pact, x: 0 }.apply(&mut game_state);
+        GameAction::PassPriority.apply(&mut game_state);
+        assert_eq!(game_state.active_player.graveyard.spells, vec![pact]);
+    }
+
+    #[test]
+    fn spells_are_cast_from_hand_with_the_right_timing() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let amulet = Spell::Permanent(Permanent::AmuletOfVigor);
+        let explore = Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::Explore));
+        let pact = Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact));
+        game_state.active_player.hand.spells = vec![explore, amulet, pact];
+        game_state.active_player.mana_pool = ManaPool { green: 1, colorless: 1, ..ManaPool::default() };
+
+        game_state.turn.step = Step::Combat;
+        let in_combat = game_state.snapshot();
+        GameAction::CastSpell { spell: explore, x: 0 }.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), in_combat);
+        let cast_pact = GameAction::CastSpell { spell: pact, x: 0 }.apply(&mut game_state);
+        assert_eq!(game_state.stack.objects, vec![StackObject::Spell { spell: pact, x: 0 }]);
+        cast_pact.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), in_combat);
+
+        game_state.turn.step = Step::PrecombatMain;
+        let before = game_state.snapshot();
+        let cast_explore = GameAction::CastSpell { spell: explore, x: 0 }.apply(&mut game_state);
+        assert_eq!(game_state.active_player.hand.spells, vec![amulet, pact]);
+        assert!(game_state.active_player.mana_pool.is_empty());
+        let cast = game_state.snapshot();
+        GameAction::CastSpell { spell: amulet, x: 0 }.apply(&mut game_state);
+        let scapeshift = Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::Scapeshift));
+        GameAction::CastSpell { spell: scapeshift, x: 0 }.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), cast);
+
+        cast_explore.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn priority_cant_be_passed_while_a_decision_is_pending() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let amulet = Spell::Permanent(Permanent::AmuletOfVigor);
+        game_state.stack.objects.push(StackObject::Spell { spell: amulet, x: 0 });
+        game_state.decision = Some(Decision::ReturnLandToHand);
+        let before = game_state.snapshot();
+        let result = GameAction::PassPriority.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+        result.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn passing_on_an_empty_stack_moves_through_the_turn() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::Forest); 5]);
+        let forest = game_state.add_test_land(Land::Forest, TapState::Tapped);
+        game_state.active_player.battlefield.land_plays = 0;
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Green, 1);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PassPriority.apply(&mut game_state)];
+        assert_eq!(game_state.turn.step, Step::Combat);
+        assert!(game_state.active_player.mana_pool.is_empty());
+        while game_state.turn.step != Step::PrecombatMain {
+            results.push(GameAction::PassPriority.apply(&mut game_state));
+        }
+        assert_eq!(game_state.turn.number, 2);
+        assert_eq!(game_state.active_player.battlefield.tap_state(forest), Some(TapState::Untapped));
+        assert_eq!(game_state.active_player.battlefield.land_plays, 1);
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::Forest]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn lands_can_only_be_played_with_a_land_play_at_sorcery_speed() {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.active_player.hand.lands = vec![Land::Forest, Land::Forest];
+        GameAction::PlayLand(Land::Forest).apply(&mut game_state);
+        let after_first = game_state.snapshot();
+        GameAction::PlayLand(Land::Forest).apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), after_first);
+
+        game_state.active_player.battlefield.land_plays = 1;
+        let with_land_play = game_state.snapshot();
+        GameAction::PlayLand(Land::GruulTurf).apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), with_land_play);
+
+        game_state.turn.step = Step::Combat;
+        let in_combat = game_state.snapshot();
+        let result = GameAction::PlayLand(Land::Forest).apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), in_combat);
+        result.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), in_combat);
+    }
+
+    #[test]
+    fn creatures_attack_once_per_combat_after_their_first_turn() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let titan = game_state.add_test_permanent(Permanent::PrimevalTitan);
+        game_state.turn.step = Step::Combat;
+        game_state.active_player.battlefield.non_lands.get_mut(&titan).unwrap().entered_turn = 1;
+        GameAction::DeclareAttackers(vec![titan]).apply(&mut game_state);
+        assert_eq!(game_state.active_player.battlefield.tap_state(titan), Some(TapState::Untapped));
+        assert!(game_state.stack.objects.is_empty());
+
+        game_state.turn.attackers_declared = false;
+        game_state.turn.number = 2;
+        let before = game_state.snapshot();
+        let attack = GameAction::DeclareAttackers(vec![titan]).apply(&mut game_state);
+        assert_eq!(game_state.active_player.battlefield.tap_state(titan), Some(TapState::Tapped));
+        assert_eq!(game_state.stack.objects.len(), 1);
+        assert!(game_state.turn.attackers_declared);
+
+        let declared = game_state.snapshot();
+        let again = GameAction::DeclareAttackers(vec![titan]).apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), declared);
+        again.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), declared);
+
+        attack.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn attackers_can_only_be_declared_in_combat() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let titan = game_state.add_test_permanent(Permanent::PrimevalTitan);
+        game_state.turn.number = 2;
+        let before = game_state.snapshot();
+        GameAction::DeclareAttackers(vec![titan]).apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+}
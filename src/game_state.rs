@@ -1,6 +1,9 @@
 // Game state module for Magic: The Gathering Amulet Titan simulation
 
-use crate::cards::{Card, Land, Spell, Permanent, CardType, card_type};
+use crate::cards::{Card, Land, Spell, Permanent, CardType, HasManaValue, card_type};
+use crate::abilities::Ability;
+use crate::mana::ManaType;
+use crate::zobrist::{graveyard_key, hand_key, object_key, stack_key};
 use rand::rngs::StdRng;
 use rand::SeedableRng;
 use enum_map::EnumMap;
@@ -17,9 +20,25 @@ pub struct GameState {
     pub stack: Stack,
     pub priority: PlayerId,
     pub next_id: usize,
+    pub decision: Option<Decision>,
+    pub priority_passes: usize,
+    pub turn: Turn,
+    pub delayed_triggers: Vec<Trigger>,
+    pub outcome: Option<GameOutcome>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameOutcome {
+    Won,
+    Lost(LossReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LossReason {
+    UnpaidPact,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PlayerId {
     Active,
     NonActive,
@@ -35,14 +54,83 @@ pub struct Player {
     pub mana_pool: ManaPool,
 }
 
+// ============================================================================
+// TURN STRUCTURE
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Step {
+    Untap,
+    Upkeep,
+    Draw,
+    PrecombatMain,
+    Combat,
+    PostcombatMain,
+    End,
+    Cleanup,
+}
+
+impl Step {
+    /// Returns the step after this one, wrapping from cleanup to the next untap
+    pub fn next(self) -> Step {
+        match self {
+            Step::Untap => Step::Upkeep,
+            Step::Upkeep => Step::Draw,
+            Step::Draw => Step::PrecombatMain,
+            Step::PrecombatMain => Step::Combat,
+            Step::Combat => Step::PostcombatMain,
+            Step::PostcombatMain => Step::End,
+            Step::End => Step::Cleanup,
+            Step::Cleanup => Step::Untap,
+        }
+    }
+
+    /// Returns true if players receive priority during this step
+    pub fn has_priority(self) -> bool {
+        !matches!(self, Step::Untap | Step::Cleanup)
+    }
+
+    /// Returns true for the two main phases
+    pub fn is_main_phase(self) -> bool {
+        matches!(self, Step::PrecombatMain | Step::PostcombatMain)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Turn {
+    pub number: usize,
+    pub step: Step,
+    pub on_the_play: bool,
+    pub attackers_declared: bool,
+}
+
+impl Turn {
+    /// The first turn of the game, starting in the upkeep
+    pub fn first(on_the_play: bool) -> Self {
+        Turn {
+            number: 1,
+            step: Step::Upkeep,
+            on_the_play,
+            attackers_declared: false,
+        }
+    }
+
+    /// Returns true if the active player skips this turn's draw
+    pub fn skips_draw(&self) -> bool {
+        self.number == 1 && self.on_the_play
+    }
+}
+
 // ============================================================================
 // GRAVEYARD
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Graveyard {
     pub spells: Vec<Spell>,
     pub lands: Vec<Land>,
+    /// Zobrist hash of the cards, kept up to date by the methods below
+    hash: u64,
 }
 
 
@@ -50,24 +138,105 @@ pub struct Graveyard {
 // HAND
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Hand {
     pub lands: Vec<Land>,
     pub spells: Vec<Spell>,
+    /// Zobrist hash of the cards, kept up to date by the methods below
+    hash: u64,
+}
+
+impl Hand {
+    /// Creates a hand holding the given cards
+    pub fn new(lands: Vec<Land>, spells: Vec<Spell>) -> Self {
+        let mut hand = Hand::default();
+        lands.into_iter().for_each(|land| hand.add_card(Card::Land(land)));
+        spells.into_iter().for_each(|spell| hand.add_card(Card::Spell(spell)));
+        hand
+    }
+
+    /// Returns the Zobrist hash of the cards in hand
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Removes a land from hand, returning the index it was removed from
+    pub fn remove_land(&mut self, land: Land) -> Option<usize> {
+        let index = self.lands.iter().position(|&l| l == land)?;
+        self.lands.remove(index);
+        self.hash = self.hash.wrapping_sub(hand_key(Card::Land(land)));
+        Some(index)
+    }
+
+    /// Removes a card from hand, returning the index it was removed from
+    /// within its list
+    pub fn remove_card(&mut self, card: Card) -> Option<usize> {
+        match card {
+            Card::Land(land) => self.remove_land(land),
+            Card::Spell(spell) => {
+                let index = self.spells.iter().position(|&s| s == spell)?;
+                self.spells.remove(index);
+                self.hash = self.hash.wrapping_sub(hand_key(card));
+                Some(index)
+            }
+        }
+    }
+
+    /// Puts a card back into hand at the index it was removed from
+    pub fn insert_card(&mut self, index: usize, card: Card) {
+        match card {
+            Card::Land(land) => self.lands.insert(index, land),
+            Card::Spell(spell) => self.spells.insert(index, spell),
+        }
+        self.hash = self.hash.wrapping_add(hand_key(card));
+    }
+
+    /// Returns true if the hand contains the card
+    pub fn contains(&self, card: Card) -> bool {
+        match card {
+            Card::Land(land) => self.lands.contains(&land),
+            Card::Spell(spell) => self.spells.contains(&spell),
+        }
+    }
+
+    /// Removes the most recently added copy of a card from hand
+    pub fn remove_last(&mut self, card: Card) {
+        let removed = match card {
+            Card::Land(land) => self.lands.iter().rposition(|&l| l == land).map(|pos| self.lands.remove(pos)).is_some(),
+            Card::Spell(spell) => {
+                self.spells.iter().rposition(|&s| s == spell).map(|pos| self.spells.remove(pos)).is_some()
+            }
+        };
+        if removed {
+            self.hash = self.hash.wrapping_sub(hand_key(card));
+        }
+    }
+
+    /// Adds a card to hand
+    pub fn add_card(&mut self, card: Card) {
+        match card {
+            Card::Land(land) => self.lands.push(land),
+            Card::Spell(spell) => self.spells.push(spell),
+        }
+        self.hash = self.hash.wrapping_add(hand_key(card));
+    }
 }
 
 // ============================================================================
 // BATTLEFIELD
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Battlefield {
     pub lands: HashMap<GameObjectId, GameObject<Land>>,
     pub non_lands: HashMap<GameObjectId, GameObject<Permanent>>,
     pub land_plays: usize,
+    /// Zobrist hash of the permanents, ignoring their ids, kept up to date by
+    /// the methods below
+    hash: u64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TapState {
     Tapped,
     Untapped,
@@ -77,25 +246,343 @@ pub enum TapState {
 pub struct GameObject<A> {
     pub permanent: A,
     pub tap_state: TapState,
+    pub entered_turn: usize,
+    pub lore_counters: u8,
+    pub token: bool,
+    /// The card whose characteristics this object has copied, if any
+    pub copy_of: Option<Card>,
+    /// The copy effect ends at the end of the turn (Shifting Woodland)
+    pub copy_until_end_of_turn: bool,
+    /// Has haste until the end of the turn
+    pub haste: bool,
+    /// Was declared as an attacker this combat
+    pub attacking: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GameObjectId(usize);
 
+impl<A> GameObject<A> {
+    /// Creates an object with the given tap state and no other status
+    pub fn new(permanent: A, tap_state: TapState) -> Self {
+        GameObject {
+            permanent,
+            tap_state,
+            entered_turn: 0,
+            lore_counters: 0,
+            token: false,
+            copy_of: None,
+            copy_until_end_of_turn: false,
+            haste: false,
+            attacking: false,
+        }
+    }
+
+    /// Converts the object's permanent while keeping its state
+    pub fn map<B>(self, f: impl FnOnce(A) -> B) -> GameObject<B> {
+        GameObject {
+            permanent: f(self.permanent),
+            tap_state: self.tap_state,
+            entered_turn: self.entered_turn,
+            lore_counters: self.lore_counters,
+            token: self.token,
+            copy_of: self.copy_of,
+            copy_until_end_of_turn: self.copy_until_end_of_turn,
+            haste: self.haste,
+            attacking: self.attacking,
+        }
+    }
+}
+
+impl GameObject<Card> {
+    /// Returns the card this object currently is, following copy effects
+    pub fn card(&self) -> Card {
+        self.copy_of.unwrap_or(self.permanent)
+    }
+
+    /// Returns true if this creature is untapped and has either been under
+    /// its controller's control since the start of the turn or has haste.
+    /// Lands that are copying a creature can attack too.
+    pub fn can_attack(&self, turn: &Turn) -> bool {
+        card_type(self.card()).contains(CardType::CREATURE)
+            && self.tap_state == TapState::Untapped
+            && (self.entered_turn < turn.number || self.haste)
+    }
+}
+
+impl GameObject<Land> {
+    /// Returns the card this object currently is, following copy effects
+    pub fn card(&self) -> Card {
+        self.copy_of.unwrap_or(Card::Land(self.permanent))
+    }
+}
+
+impl GameObject<Permanent> {
+    /// Returns the card this object currently is, following copy effects
+    pub fn card(&self) -> Card {
+        self.copy_of.unwrap_or(Card::Spell(Spell::Permanent(self.permanent)))
+    }
+}
+
+impl Battlefield {
+    /// Returns the Zobrist hash of the permanents
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns the ids of every tapped permanent, in id order
+    pub fn tapped(&self) -> Vec<GameObjectId> {
+        let lands = self.lands.iter().map(|(&id, land)| (id, land.tap_state));
+        let non_lands = self.non_lands.iter().map(|(&id, permanent)| (id, permanent.tap_state));
+        let mut tapped: Vec<GameObjectId> = lands
+            .chain(non_lands)
+            .filter(|(_, tap_state)| *tap_state == TapState::Tapped)
+            .map(|(id, _)| id)
+            .collect();
+        tapped.sort();
+        tapped
+    }
+
+    /// Returns every permanent with the card it currently is, following copy
+    /// effects, in no particular order
+    pub fn cards(&self) -> impl Iterator<Item = (GameObjectId, Card)> + '_ {
+        let lands = self.lands.iter().map(|(&id, land)| (id, land.card()));
+        let non_lands = self.non_lands.iter().map(|(&id, permanent)| (id, permanent.card()));
+        lands.chain(non_lands)
+    }
+
+    /// Returns a copy of a land or non-land permanent as a card object
+    pub fn get(&self, id: GameObjectId) -> Option<GameObject<Card>> {
+        if let Some(land) = self.lands.get(&id) {
+            return Some(land.clone().map(Card::Land));
+        }
+        self.non_lands
+            .get(&id)
+            .map(|permanent| permanent.clone().map(|p| Card::Spell(Spell::Permanent(p))))
+    }
+
+    /// Returns true if the permanent is a creature that can attack this turn
+    pub fn can_attack(&self, id: GameObjectId, turn: &Turn) -> bool {
+        self.get(id).is_some_and(|object| object.can_attack(turn))
+    }
+
+    /// Returns the ids of every creature that can attack this turn, including
+    /// lands copying a creature, in id order
+    pub fn attackers(&self, turn: &Turn) -> Vec<GameObjectId> {
+        let mut ids: Vec<GameObjectId> =
+            self.cards().map(|(id, _)| id).filter(|&id| self.can_attack(id, turn)).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Removes a land or non-land permanent from the battlefield
+    pub fn remove(&mut self, id: GameObjectId) -> Option<GameObject<Card>> {
+        let object = match self.lands.remove(&id) {
+            Some(land) => land.map(Card::Land),
+            None => self.non_lands.remove(&id)?.map(|p| Card::Spell(Spell::Permanent(p))),
+        };
+        self.hash = self.hash.wrapping_sub(object_key(&object));
+        Some(object)
+    }
+
+    /// Puts a permanent onto the battlefield under the given id, returning
+    /// false for cards that can't be permanents
+    pub fn insert(&mut self, id: GameObjectId, object: GameObject<Card>) -> bool {
+        let key = object_key(&object);
+        let inserted = match object.permanent {
+            Card::Land(land) => {
+                self.lands.insert(id, object.map(|_| land));
+                true
+            }
+            Card::Spell(Spell::Permanent(permanent)) => {
+                self.non_lands.insert(id, object.map(|_| permanent));
+                true
+            }
+            Card::Spell(Spell::NonPermanent(_)) => false,
+        };
+        if inserted {
+            self.hash = self.hash.wrapping_add(key);
+        }
+        inserted
+    }
+
+    /// Changes a permanent by taking it off and putting it back, so the hash
+    /// follows the change
+    fn modify<R>(&mut self, id: GameObjectId, f: impl FnOnce(&mut GameObject<Card>) -> R) -> Option<R> {
+        let mut object = self.remove(id)?;
+        let result = f(&mut object);
+        self.insert(id, object);
+        Some(result)
+    }
+
+    /// Sets the card a permanent is copying and whether that lasts until end
+    /// of turn, returning the previous copy effect
+    pub fn set_copy_of(
+        &mut self,
+        id: GameObjectId,
+        copy_of: Option<Card>,
+        until_end_of_turn: bool,
+    ) -> Option<(Option<Card>, bool)> {
+        self.modify(id, |object| {
+            (
+                std::mem::replace(&mut object.copy_of, copy_of),
+                std::mem::replace(&mut object.copy_until_end_of_turn, until_end_of_turn),
+            )
+        })
+    }
+
+    /// Sets whether a permanent has haste, returning its previous value
+    pub fn set_haste(&mut self, id: GameObjectId, haste: bool) -> Option<bool> {
+        self.modify(id, |object| std::mem::replace(&mut object.haste, haste))
+    }
+
+    /// Sets whether a permanent is attacking, returning its previous value
+    pub fn set_attacking(&mut self, id: GameObjectId, attacking: bool) -> Option<bool> {
+        self.modify(id, |object| std::mem::replace(&mut object.attacking, attacking))
+    }
+
+    /// Sets the number of lore counters on a permanent, returning the previous
+    /// number
+    pub fn set_lore_counters(&mut self, id: GameObjectId, lore_counters: u8) -> Option<u8> {
+        self.modify(id, |object| std::mem::replace(&mut object.lore_counters, lore_counters))
+    }
+
+    /// Returns the ids of every attacking creature, including lands copying a
+    /// creature, in id order
+    pub fn attacking(&self) -> Vec<GameObjectId> {
+        let lands = self.lands.iter().map(|(&id, land)| (id, land.attacking));
+        let non_lands = self.non_lands.iter().map(|(&id, permanent)| (id, permanent.attacking));
+        let mut ids: Vec<GameObjectId> =
+            lands.chain(non_lands).filter(|&(_, attacking)| attacking).map(|(id, _)| id).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Returns the ids of every permanent with an effect that ends this turn,
+    /// in id order
+    pub fn until_end_of_turn(&self) -> Vec<GameObjectId> {
+        let lands = self.lands.iter().map(|(&id, land)| (id, land.haste || land.copy_until_end_of_turn));
+        let non_lands = self
+            .non_lands
+            .iter()
+            .map(|(&id, permanent)| (id, permanent.haste || permanent.copy_until_end_of_turn));
+        let mut ids: Vec<GameObjectId> = lands.chain(non_lands).filter(|&(_, ends)| ends).map(|(id, _)| id).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Returns the tap state of a land or non-land permanent
+    pub fn tap_state(&self, id: GameObjectId) -> Option<TapState> {
+        self.lands
+            .get(&id)
+            .map(|land| land.tap_state)
+            .or_else(|| self.non_lands.get(&id).map(|permanent| permanent.tap_state))
+    }
+
+    /// Sets the tap state of a permanent, returning its previous tap state
+    pub fn set_tap_state(&mut self, id: GameObjectId, tap_state: TapState) -> Option<TapState> {
+        self.modify(id, |object| std::mem::replace(&mut object.tap_state, tap_state))
+    }
+}
+
 impl GameState {
+    /// Returns the number of players in the game
+    pub fn player_count(&self) -> usize {
+        if self.non_active_player.is_some() { 2 } else { 1 }
+    }
+
+    /// Returns the player with the given id, if they're in the game
+    pub fn player(&self, player: PlayerId) -> Option<&Player> {
+        match player {
+            PlayerId::Active => Some(&self.active_player),
+            PlayerId::NonActive => self.non_active_player.as_ref(),
+        }
+    }
+
+    /// Returns the player with the given id mutably, if they're in the game
+    pub fn player_mut(&mut self, player: PlayerId) -> Option<&mut Player> {
+        match player {
+            PlayerId::Active => Some(&mut self.active_player),
+            PlayerId::NonActive => self.non_active_player.as_mut(),
+        }
+    }
+
+    /// Returns the player controlling a permanent on either battlefield
+    pub fn controller(&self, id: GameObjectId) -> Option<PlayerId> {
+        [PlayerId::Active, PlayerId::NonActive].into_iter().find(|&player| {
+            self.player(player).is_some_and(|player| {
+                let battlefield = &player.battlefield;
+                battlefield.lands.contains_key(&id) || battlefield.non_lands.contains_key(&id)
+            })
+        })
+    }
+
+    /// Returns the card a permanent on either battlefield currently is,
+    /// following copy effects
+    pub fn permanent(&self, id: GameObjectId) -> Option<Card> {
+        let battlefield = &self.player(self.controller(id)?)?.battlefield;
+        battlefield
+            .lands
+            .get(&id)
+            .map(|land| land.card())
+            .or_else(|| battlefield.non_lands.get(&id).map(|permanent| permanent.card()))
+    }
+
+    /// Returns true if the active player could cast a sorcery right now: it's
+    /// their main phase, they have priority and the stack is empty
+    pub fn is_sorcery_speed(&self) -> bool {
+        self.turn.step.is_main_phase() && self.priority == PlayerId::Active && self.stack.objects.is_empty()
+    }
+
+    /// Returns true if the active player can cast a spell from their hand with
+    /// the given X: it's in hand, the timing is right and the mana is there
+    pub fn can_cast(&self, spell: Spell, x: u8) -> bool {
+        let instant = card_type(Card::Spell(spell)).contains(CardType::INSTANT);
+        self.decision.is_none()
+            && self.active_player.hand.spells.contains(&spell)
+            && (instant || self.is_sorcery_speed())
+            && self.active_player.mana_pool.can_pay(&spell.mana_value(), x)
+    }
+
     /// Generates a new unique GameObjectId
     pub fn next_game_object_id(&mut self) -> GameObjectId {
         let id = GameObjectId(self.next_id);
         self.next_id += 1;
         id
     }
+
+    /// Returns true if the active player can play a land from their hand: it's
+    /// in hand, a land play is left and the timing is right
+    pub fn can_play_land(&self, land: Land) -> bool {
+        self.decision.is_none()
+            && self.active_player.battlefield.land_plays > 0
+            && self.active_player.hand.lands.contains(&land)
+            && self.is_sorcery_speed()
+    }
+
+    /// Returns true if the active player can declare attackers: it's their
+    /// combat step, they haven't attacked yet, they have priority and nothing
+    /// is on the stack or waiting for a decision
+    pub fn can_declare_attackers(&self) -> bool {
+        self.turn.step == Step::Combat
+            && !self.turn.attackers_declared
+            && self.priority == PlayerId::Active
+            && self.stack.objects.is_empty()
+            && self.decision.is_none()
+    }
+
+    /// Hands back an id from next_game_object_id once the object it was
+    /// generated for has been reverted, so a replayed line gets the same ids
+    pub fn release_game_object_id(&mut self, id: GameObjectId) {
+        self.next_id = id.0;
+    }
 }
 
 // ============================================================================
 // MANA POOL
 // ============================================================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ManaPool {
     pub white: usize,
     pub blue: usize,
@@ -111,45 +598,174 @@ pub struct ManaPool {
 // STACK
 // ============================================================================
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Trigger {
     Enters(Card),
+    Attacks(Card),
     AmuletUntap(GameObjectId),
+    /// Summoner's Pact's upkeep trigger: pay {2}{G}{G} or lose the game
+    PactPayment,
+    /// A Saga reaching one of its chapters
+    Chapter {
+        source: GameObjectId,
+        chapter: u8,
+    },
+    /// Vesuva or Echoing Deeps choosing a land to copy as it enters
+    EntersAsCopy(GameObjectId),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct StackObjectId(usize);
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Target {
     Object(GameObjectId),
     Spell(StackObjectId),
+    /// A card in the active player's graveyard
+    Card(Card),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Where an activated ability is activated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbilitySource {
+    Battlefield(GameObjectId),
+    /// Channel abilities discard the card from hand as a cost
+    Hand(Card),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum StackObject {
-    Spell(Spell),
+    Spell {
+        spell: Spell,
+        x: u8,
+    },
     Trigger(Trigger),
     ActivatedAbility {
-        source: GameObjectId,
+        source: AbilitySource,
+        ability: Ability,
         target: Option<Target>,
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Stack {
     pub objects: Vec<StackObject>,
+    /// Zobrist hash of the objects and their depths, kept up to date by the
+    /// methods below
+    hash: u64,
+}
+
+impl Stack {
+    /// Returns the Zobrist hash of the stack
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Puts an object on top of the stack
+    pub fn push(&mut self, object: StackObject) {
+        self.hash = self.hash.wrapping_add(stack_key(&object, self.objects.len()));
+        self.objects.push(object);
+    }
+
+    /// Takes the top object off the stack
+    pub fn pop(&mut self) -> Option<StackObject> {
+        let object = self.objects.pop()?;
+        self.hash = self.hash.wrapping_sub(stack_key(&object, self.objects.len()));
+        Some(object)
+    }
+
+    /// Takes objects off the top until `len` are left
+    pub fn truncate(&mut self, len: usize) {
+        while self.objects.len() > len {
+            self.pop();
+        }
+    }
+
+    /// Puts objects on the stack in order, the last ending up on top
+    pub fn extend(&mut self, objects: impl IntoIterator<Item = StackObject>) {
+        objects.into_iter().for_each(|object| self.push(object));
+    }
+}
+
+// ============================================================================
+// DECISIONS
+// ============================================================================
+
+/// Which cards a library search may find
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchFilter {
+    Land,
+    GreenCreature { max_mana_value: Option<usize> },
+    Artifact { max_mana_value: usize },
+    ManaValue(usize),
+}
+
+/// Where cards found by a library search are put
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Destination {
+    Hand,
+    Battlefield(TapState),
+    LibraryTop,
+}
+
+/// A choice the engine is waiting on before play can continue
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Decision {
+    /// Return a land you control to its owner's hand (karoo lands)
+    ReturnLandToHand,
+    /// Sacrifice any number of lands, then search for that many lands
+    Scapeshift,
+    /// You may put a land card from your hand onto the battlefield. With
+    /// `repeat`, doing so draws a card and asks again (Cultivator Colossus).
+    PutLandFromHand {
+        tap_state: TapState,
+        repeat: bool,
+    },
+    /// Search the library for up to `count` cards matching `filter`
+    SearchLibrary {
+        filter: SearchFilter,
+        count: usize,
+        destination: Destination,
+    },
+    /// You may have `source` become a copy of a land on the battlefield
+    CopyLand { source: GameObjectId },
+    /// You may have `source` become a copy of a land card in a graveyard
+    CopyLandFromGraveyard { source: GameObjectId },
+    /// Add one mana of any color (Crumbling Vestige)
+    AddManaOfAnyColor,
+    /// Sacrifice `count` untapped lands, or as many as you have (Lotus Field)
+    SacrificeUntappedLands { count: usize },
+}
+
+/// The caller's answer to a pending Decision
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    Objects(Vec<GameObjectId>),
+    Cards(Vec<Card>),
+    Color(ManaType),
 }
 
 // ============================================================================
 // LIBRARY
 // ============================================================================
 
+/// Where a card was taken out of the library from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryPosition {
+    /// Somewhere in the shuffled, unknown part
+    Unknown,
+    /// At an index of the known top cards
+    Top(usize),
+}
+
 #[derive(Debug, Clone)]
 pub struct Library {
     pub cards: EnumMap<Card, u8>,
     pub size: usize,
     pub rng: StdRng,
+    /// Cards in a known order on top of the library, the last one on top.
+    /// These aren't counted in `cards` or `size`.
+    pub top: Vec<Card>,
 }
 
 impl Library {
@@ -165,17 +781,58 @@ impl Library {
             cards: card_counts,
             size,
             rng,
+            top: Vec::new(),
         }
     }
 
+    /// Creates a library whose cards are drawn in the given order, first card
+    /// first, so every draw is known in advance
+    pub fn ordered(cards: Vec<Card>) -> Self {
+        let mut library = Library::new(Vec::new(), 0);
+        library.top = cards.into_iter().rev().collect();
+        library
+    }
+
+    /// Returns how many copies of a card are in the library
+    pub fn count(&self, card: Card) -> usize {
+        self.cards[card] as usize + self.top.iter().filter(|&&c| c == card).count()
+    }
+
     /// Returns the number of cards in the library
     pub fn len(&self) -> usize {
-        self.size
+        self.size + self.top.len()
     }
 
     /// Returns true if the library is empty
     pub fn is_empty(&self) -> bool {
-        self.size == 0
+        self.len() == 0
+    }
+
+    /// Draws the top card if it's known, otherwise a random card
+    pub fn draw_card(&mut self) -> Option<Card> {
+        self.top.pop().or_else(|| self.draw_random_card())
+    }
+
+    /// Puts a card on top of the library
+    pub fn put_on_top(&mut self, card: Card) {
+        self.top.push(card);
+    }
+
+    /// Takes the known top card off the library
+    pub fn take_top(&mut self) -> Option<Card> {
+        self.top.pop()
+    }
+
+    /// Puts back cards taken with draw_card, the first `from_top` of which
+    /// were known top cards
+    pub fn undraw(&mut self, cards: &[Card], from_top: usize) {
+        for (index, &card) in cards.iter().enumerate().rev() {
+            if index < from_top {
+                self.put_on_top(card);
+            } else {
+                self.add_card(card);
+            }
+        }
     }
     /// Draws a random card from the library, returns None if library is empty
     pub fn draw_random_card(&mut self) -> Option<Card> {
@@ -210,6 +867,28 @@ impl Library {
         self.cards[card] += 1;
         self.size += 1;
     }
+
+    /// Removes a card from the library, returning where it was taken from.
+    /// Copies in unknown positions are taken before known top cards, and the
+    /// deepest known copy before the ones that will be drawn sooner.
+    pub fn remove_card(&mut self, card: Card) -> Option<LibraryPosition> {
+        if self.cards[card] == 0 {
+            let index = self.top.iter().position(|&c| c == card)?;
+            self.top.remove(index);
+            return Some(LibraryPosition::Top(index));
+        }
+        self.cards[card] -= 1;
+        self.size -= 1;
+        Some(LibraryPosition::Unknown)
+    }
+
+    /// Puts a card back where remove_card took it from
+    pub fn insert_card(&mut self, card: Card, position: LibraryPosition) {
+        match position {
+            LibraryPosition::Unknown => self.add_card(card),
+            LibraryPosition::Top(index) => self.top.insert(index, card),
+        }
+    }
 }
 
 // ============================================================================
@@ -217,6 +896,61 @@ impl Library {
 // ============================================================================
 
 impl Graveyard {
+    /// Returns the Zobrist hash of the cards in the graveyard
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Removes a card from the graveyard, returning the index it was removed
+    /// from within its list
+    pub fn remove_card(&mut self, card: Card) -> Option<usize> {
+        let index = match card {
+            Card::Land(land) => {
+                let index = self.lands.iter().position(|&l| l == land)?;
+                self.lands.remove(index);
+                index
+            }
+            Card::Spell(spell) => {
+                let index = self.spells.iter().position(|&s| s == spell)?;
+                self.spells.remove(index);
+                index
+            }
+        };
+        self.hash = self.hash.wrapping_sub(graveyard_key(card));
+        Some(index)
+    }
+
+    /// Puts a card back into the graveyard at the index it was removed from
+    pub fn insert_card(&mut self, index: usize, card: Card) {
+        match card {
+            Card::Land(land) => self.lands.insert(index, land),
+            Card::Spell(spell) => self.spells.insert(index, spell),
+        }
+        self.hash = self.hash.wrapping_add(graveyard_key(card));
+    }
+
+    /// Puts a card into the graveyard
+    pub fn add_card(&mut self, card: Card) {
+        match card {
+            Card::Land(land) => self.lands.push(land),
+            Card::Spell(spell) => self.spells.push(spell),
+        }
+        self.hash = self.hash.wrapping_add(graveyard_key(card));
+    }
+
+    /// Removes the most recently added copy of a card from the graveyard
+    pub fn remove_last(&mut self, card: Card) {
+        let removed = match card {
+            Card::Land(land) => self.lands.iter().rposition(|&l| l == land).map(|pos| self.lands.remove(pos)).is_some(),
+            Card::Spell(spell) => {
+                self.spells.iter().rposition(|&s| s == spell).map(|pos| self.spells.remove(pos)).is_some()
+            }
+        };
+        if removed {
+            self.hash = self.hash.wrapping_sub(graveyard_key(card));
+        }
+    }
+
     /// Returns an iterator over the cards in the graveyard
     pub fn iter(&self) -> impl Iterator<Item = Card> + '_ {
         let land_cards = self.lands.iter().map(|&land| Card::Land(land));
@@ -234,4 +968,68 @@ impl Graveyard {
             .count_ones()
             >= 4
     }
-}
\ No newline at end of file
+}
+// ============================================================================
+// TEST HELPERS
+// ============================================================================
+
+#[cfg(test)]
+impl GameState {
+    /// Creates a game in the first main phase on an empty board with `library`
+    /// as the active player's library
+    pub(crate) fn for_test(library: Vec<Card>) -> Self {
+        GameState {
+            active_player: Player {
+                life_total: 20,
+                library: Library::new(library, 0),
+                hand: Hand::default(),
+                battlefield: Battlefield {
+                    land_plays: 1,
+                    ..Battlefield::default()
+                },
+                graveyard: Graveyard::default(),
+                mana_pool: ManaPool::default(),
+            },
+            non_active_player: None,
+            stack: Stack::default(),
+            priority: PlayerId::Active,
+            next_id: 0,
+            decision: None,
+            priority_passes: 0,
+            turn: Turn {
+                step: Step::PrecombatMain,
+                ..Turn::first(true)
+            },
+            delayed_triggers: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    /// Formats the whole state with the battlefield in id order and without
+    /// the library's RNG, so states can be compared across an apply and revert
+    pub(crate) fn snapshot(&self) -> String {
+        let mut state = self.clone();
+        state.active_player.library.rng = StdRng::seed_from_u64(0);
+        let battlefield = &mut state.active_player.battlefield;
+        let lands: std::collections::BTreeMap<_, _> =
+            std::mem::take(&mut battlefield.lands).into_iter().collect();
+        let non_lands: std::collections::BTreeMap<_, _> =
+            std::mem::take(&mut battlefield.non_lands).into_iter().collect();
+        format!("{:?} {:?} {:?}", state, lands, non_lands)
+    }
+
+    /// Puts a land directly onto the active player's battlefield
+    pub(crate) fn add_test_land(&mut self, land: Land, tap_state: TapState) -> GameObjectId {
+        let id = self.next_game_object_id();
+        self.active_player.battlefield.insert(id, GameObject::new(Card::Land(land), tap_state));
+        id
+    }
+
+    /// Puts an untapped non-land permanent directly onto the active player's battlefield
+    pub(crate) fn add_test_permanent(&mut self, permanent: Permanent) -> GameObjectId {
+        let id = self.next_game_object_id();
+        let permanent = Card::Spell(Spell::Permanent(permanent));
+        self.active_player.battlefield.insert(id, GameObject::new(permanent, TapState::Untapped));
+        id
+    }
+}
@@ -0,0 +1,239 @@
+// Legal action generation for Magic: The Gathering Amulet Titan simulation
+
+use crate::abilities::ActivatedAbility;
+use crate::cards::{Card, HasManaValue, Land, Spell};
+use crate::game_action::GameAction;
+use crate::game_state::*;
+
+// ============================================================================
+// LEGAL ACTIONS
+// ============================================================================
+
+impl GameState {
+    /// Enumerates every legal action for the player with priority. A pending
+    /// decision must be answered before anything else can happen.
+    pub fn legal_actions(&self) -> Vec<GameAction> {
+        if self.outcome.is_some() {
+            return Vec::new();
+        }
+        if let Some(decision) = &self.decision {
+            return decision.options(self).into_iter().map(GameAction::Choose).collect();
+        }
+        // The opponent is a goldfish and only ever passes
+        if self.priority == PlayerId::NonActive {
+            return vec![GameAction::PassPriority];
+        }
+        let mut actions = Vec::new();
+        actions.extend(self.land_plays());
+        actions.extend(self.spell_casts());
+        actions.extend(self.ability_activations());
+        actions.extend(self.mana_ability_activations());
+        actions.extend(self.attack_declarations());
+        actions.push(GameAction::PassPriority);
+        actions
+    }
+
+    /// Returns a land play for each distinct land in hand that can be played
+    /// right now
+    fn land_plays(&self) -> Vec<GameAction> {
+        let mut lands: Vec<Land> = self.active_player.hand.lands.clone();
+        lands.sort_by_key(|&land| land as usize);
+        lands.dedup();
+        lands
+            .into_iter()
+            .filter(|&land| self.can_play_land(land))
+            .map(GameAction::PlayLand)
+            .collect()
+    }
+
+    /// Returns a cast for each distinct castable spell in hand, and for X
+    /// spells one per affordable X
+    fn spell_casts(&self) -> Vec<GameAction> {
+        let mut spells: Vec<Spell> = Vec::new();
+        for &spell in &self.active_player.hand.spells {
+            if !spells.contains(&spell) {
+                spells.push(spell);
+            }
+        }
+        let mut actions = Vec::new();
+        for spell in spells {
+            let Some(max_x) = self.active_player.mana_pool.max_x(&spell.mana_value()) else {
+                continue;
+            };
+            for x in 0..=max_x {
+                if self.can_cast(spell, x) {
+                    actions.push(GameAction::CastSpell { spell, x });
+                }
+            }
+        }
+        actions
+    }
+
+    /// Returns an activation for each activated ability that can be paid for,
+    /// once per legal target
+    fn ability_activations(&self) -> Vec<GameAction> {
+        let battlefield = &self.active_player.battlefield;
+        let mut ids: Vec<GameObjectId> = battlefield.cards().map(|(id, _)| id).collect();
+        ids.sort();
+        let mut sources: Vec<AbilitySource> = ids.into_iter().map(AbilitySource::Battlefield).collect();
+        for card in self.active_player.hand.lands.iter().map(|&land| Card::Land(land)) {
+            if !sources.contains(&AbilitySource::Hand(card)) {
+                sources.push(AbilitySource::Hand(card));
+            }
+        }
+
+        let mut actions = Vec::new();
+        for source in sources {
+            let Some(ability) = self.activated_ability(source) else {
+                continue;
+            };
+            for target in self.targets(&ability) {
+                if self.can_activate(source, &ability, target.as_ref()) {
+                    actions.push(GameAction::ActivateAbility { source, target });
+                }
+            }
+        }
+        actions
+    }
+
+    /// Returns every legal target for an ability, or just no target for
+    /// abilities that don't target
+    fn targets(&self, ability: &ActivatedAbility) -> Vec<Option<Target>> {
+        if self.is_legal_target(ability.ability, None) {
+            return vec![None];
+        }
+        let players = [Some(&self.active_player), self.non_active_player.as_ref()];
+        let mut ids: Vec<GameObjectId> = players
+            .into_iter()
+            .flatten()
+            .flat_map(|player| player.battlefield.cards().map(|(id, _)| id))
+            .collect();
+        ids.sort();
+        let mut targets: Vec<Target> = ids.into_iter().map(Target::Object).collect();
+        for card in self.active_player.graveyard.iter() {
+            if !targets.contains(&Target::Card(card)) {
+                targets.push(Target::Card(card));
+            }
+        }
+        targets
+            .into_iter()
+            .filter(|target| self.is_legal_target(ability.ability, Some(target)))
+            .map(Some)
+            .collect()
+    }
+
+    /// Returns each mana ability of the untapped lands. Lands in exactly the
+    /// same state are interchangeable, so only the first of them is offered.
+    fn mana_ability_activations(&self) -> Vec<GameAction> {
+        let battlefield = &self.active_player.battlefield;
+        let mut lands: Vec<(GameObjectId, &GameObject<Land>)> =
+            battlefield.lands.iter().map(|(&id, land)| (id, land)).collect();
+        lands.sort_by_key(|&(id, _)| id);
+
+        let mut seen: Vec<&GameObject<Land>> = Vec::new();
+        let mut actions = Vec::new();
+        for (source, land) in lands {
+            if seen.contains(&land) {
+                continue;
+            }
+            let abilities = self.available_mana_abilities(source);
+            if abilities.is_empty() {
+                continue;
+            }
+            seen.push(land);
+            actions.extend(
+                abilities
+                    .into_iter()
+                    .map(|ability| GameAction::ActivateManaAbility { source, ability }),
+            );
+        }
+        actions
+    }
+
+    /// Returns an attack with every creature that can attack, once per combat.
+    /// Nothing blocks a goldfish, so holding creatures back is never better.
+    fn attack_declarations(&self) -> Vec<GameAction> {
+        if !self.can_declare_attackers() {
+            return Vec::new();
+        }
+        let attackers = self.active_player.battlefield.attackers(&self.turn);
+        if attackers.is_empty() {
+            return Vec::new();
+        }
+        vec![GameAction::DeclareAttackers(attackers)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Instant, NonPermanent, Permanent, Sorcery};
+
+    /// Returns the sources of the mana abilities among the actions, in order
+    fn mana_sources(actions: &[GameAction]) -> Vec<GameObjectId> {
+        let mut sources: Vec<GameObjectId> = actions
+            .iter()
+            .filter_map(|action| match action {
+                GameAction::ActivateManaAbility { source, .. } => Some(*source),
+                _ => None,
+            })
+            .collect();
+        sources.dedup();
+        sources
+    }
+
+    #[test]
+    fn offers_distinct_lands_castable_spells_and_interchangeable_mana_once() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let forest = game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.add_test_land(Land::Forest, TapState::Tapped);
+        let vesuva = game_state.add_test_land(Land::Vesuva, TapState::Untapped);
+        game_state.active_player.battlefield.set_copy_of(vesuva, Some(Card::Land(Land::Forest)), false);
+        let explore = Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::Explore));
+        let pact = Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact));
+        game_state.active_player.hand.lands = vec![Land::Forest, Land::GruulTurf, Land::Forest];
+        game_state.active_player.hand.spells = vec![explore, pact, pact];
+
+        let actions = game_state.legal_actions();
+        assert_eq!(actions.iter().filter(|action| matches!(action, GameAction::PlayLand(_))).count(), 2);
+        assert!(actions.contains(&GameAction::CastSpell { spell: pact, x: 0 }));
+        assert!(!actions.contains(&GameAction::CastSpell { spell: explore, x: 0 }));
+        assert_eq!(mana_sources(&actions), vec![forest, vesuva]);
+        assert_eq!(actions.last(), Some(&GameAction::PassPriority));
+
+        game_state.active_player.battlefield.land_plays = 0;
+        let actions = game_state.legal_actions();
+        assert!(!actions.iter().any(|action| matches!(action, GameAction::PlayLand(_))));
+    }
+
+    #[test]
+    fn decisions_and_the_opponent_limit_the_actions() {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.decision = Some(Decision::AddManaOfAnyColor);
+        let actions = game_state.legal_actions();
+        assert_eq!(actions.len(), 5);
+        assert!(actions.iter().all(|action| matches!(action, GameAction::Choose(Choice::Color(_)))));
+
+        game_state.decision = None;
+        game_state.priority = PlayerId::NonActive;
+        assert_eq!(game_state.legal_actions(), vec![GameAction::PassPriority]);
+    }
+
+    #[test]
+    fn attacks_with_every_creature_that_can_once_per_combat() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let titan = game_state.add_test_permanent(Permanent::PrimevalTitan);
+        let new_titan = game_state.add_test_permanent(Permanent::PrimevalTitan);
+        game_state.active_player.battlefield.non_lands.get_mut(&new_titan).unwrap().entered_turn =
+            game_state.turn.number;
+        let attack = GameAction::DeclareAttackers(vec![titan]);
+        assert!(!game_state.legal_actions().contains(&attack));
+
+        game_state.turn.step = Step::Combat;
+        assert!(game_state.legal_actions().contains(&attack));
+        game_state.turn.attackers_declared = true;
+        assert!(!game_state.legal_actions().contains(&attack));
+    }
+}
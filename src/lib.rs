@@ -0,0 +1,10 @@
+pub mod cards;
+pub mod game_state;
+pub mod game_action;
+pub mod mana;
+pub mod abilities;
+pub mod resolution;
+pub mod legal_actions;
+pub mod solver;
+pub mod zobrist;
+pub mod simulation;
@@ -1,7 +1,32 @@
-mod cards;
-mod game_state;
-mod game_action;
+use atlas::simulation::{amulet_titan, Policy, RandomPolicy, Simulation, SolverPolicy};
+use atlas::solver::{Goal, Solver};
 
+/// States the solver may visit per solve before the game counts as no kill
+const NODE_LIMIT: usize = 1_000_000;
+
+/// Usage: atlas [GAMES] [play|draw] [solver|random]
 fn main() {
     println!("Atlas - MTG Amulet Titan Simulator");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let games = args.first().and_then(|games| games.parse().ok()).unwrap_or(100);
+    let on_the_play = args.get(1).is_none_or(|play| play != "draw");
+    let simulation = Simulation {
+        deck: amulet_titan(),
+        games,
+        on_the_play,
+        goal: Goal::TitanAttack,
+        max_turn: 4,
+        seed: 0,
+    };
+    let mut policy: Box<dyn Policy> = match args.get(2).map(String::as_str) {
+        Some("random") => Box::new(RandomPolicy::new(simulation.seed)),
+        _ => {
+            let solver = Solver::new(simulation.goal, simulation.max_turn).with_node_limit(NODE_LIMIT);
+            Box::new(SolverPolicy::new(solver))
+        }
+    };
+
+    println!("Goldfishing {} games {}", games, if on_the_play { "on the play" } else { "on the draw" });
+    print!("{}", simulation.run(policy.as_mut()));
 }
@@ -0,0 +1,240 @@
+// Mana payment for Magic: The Gathering Amulet Titan simulation
+
+use crate::cards::ManaValue;
+use crate::game_state::ManaPool;
+
+// ============================================================================
+// MANA TYPES
+// ============================================================================
+
+/// The six types of mana a pool can hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ManaType {
+    White,
+    Blue,
+    Black,
+    Red,
+    Green,
+    Colorless,
+}
+
+impl ManaType {
+    pub const ALL: [ManaType; 6] = [
+        ManaType::White,
+        ManaType::Blue,
+        ManaType::Black,
+        ManaType::Red,
+        ManaType::Green,
+        ManaType::Colorless,
+    ];
+
+    /// The five colors, in WUBRG order
+    pub const COLORS: [ManaType; 5] = [
+        ManaType::White,
+        ManaType::Blue,
+        ManaType::Black,
+        ManaType::Red,
+        ManaType::Green,
+    ];
+}
+
+// ============================================================================
+// MANA POOL IMPLEMENTATION
+// ============================================================================
+
+impl ManaPool {
+    /// Creates a pool holding `amount` mana of a single type
+    pub fn of(mana_type: ManaType, amount: usize) -> Self {
+        let mut pool = ManaPool::default();
+        *pool.get_mut(mana_type) = amount;
+        pool
+    }
+
+    /// Returns the amount of the given mana type in the pool
+    pub fn get(&self, mana_type: ManaType) -> usize {
+        match mana_type {
+            ManaType::White => self.white,
+            ManaType::Blue => self.blue,
+            ManaType::Black => self.black,
+            ManaType::Red => self.red,
+            ManaType::Green => self.green,
+            ManaType::Colorless => self.colorless,
+        }
+    }
+
+    /// Returns a mutable reference to the amount of the given mana type
+    pub fn get_mut(&mut self, mana_type: ManaType) -> &mut usize {
+        match mana_type {
+            ManaType::White => &mut self.white,
+            ManaType::Blue => &mut self.blue,
+            ManaType::Black => &mut self.black,
+            ManaType::Red => &mut self.red,
+            ManaType::Green => &mut self.green,
+            ManaType::Colorless => &mut self.colorless,
+        }
+    }
+
+    /// Returns the total amount of mana in the pool
+    pub fn total(&self) -> usize {
+        ManaType::ALL.iter().map(|&mana_type| self.get(mana_type)).sum()
+    }
+
+    /// Returns true if the pool holds no mana
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Adds all mana from another pool to this one
+    pub fn add(&mut self, other: &ManaPool) {
+        for mana_type in ManaType::ALL {
+            *self.get_mut(mana_type) += other.get(mana_type);
+        }
+    }
+
+    /// Removes all mana in another pool from this one, saturating at zero
+    pub fn remove(&mut self, other: &ManaPool) {
+        for mana_type in ManaType::ALL {
+            let amount = self.get_mut(mana_type);
+            *amount = amount.saturating_sub(other.get(mana_type));
+        }
+    }
+
+    /// Returns true if every mana in `other` is also available in this pool
+    pub fn contains(&self, other: &ManaPool) -> bool {
+        ManaType::ALL
+            .iter()
+            .all(|&mana_type| self.get(mana_type) >= other.get(mana_type))
+    }
+
+    /// Chooses the exact mana to spend on `cost` with X set to `x`.
+    ///
+    /// Colored pips and {C} must be paid with their own type. Generic costs
+    /// (including X) are paid with colorless first, then with whichever color
+    /// the pool has the most of, so the colors most likely to be needed later
+    /// are kept. Returns None if the pool cannot cover the cost.
+    pub fn payment(&self, cost: &ManaValue, x: u8) -> Option<ManaPool> {
+        let mut remaining = self.clone();
+        let mut payment = ManaPool::default();
+
+        for mana_type in ManaType::ALL {
+            let required = pip_count(cost, mana_type);
+            let available = remaining.get_mut(mana_type);
+            if *available < required {
+                return None;
+            }
+            *available -= required;
+            *payment.get_mut(mana_type) += required;
+        }
+
+        let mut generic = cost.generic as usize + cost.x as usize * x as usize;
+        let colorless = generic.min(remaining.colorless);
+        remaining.colorless -= colorless;
+        payment.colorless += colorless;
+        generic -= colorless;
+
+        while generic > 0 {
+            let color = ManaType::COLORS
+                .into_iter()
+                .filter(|&color| remaining.get(color) > 0)
+                .max_by_key(|&color| remaining.get(color))?;
+            *remaining.get_mut(color) -= 1;
+            *payment.get_mut(color) += 1;
+            generic -= 1;
+        }
+
+        Some(payment)
+    }
+
+    /// Returns true if the pool can pay `cost` with X set to `x`
+    pub fn can_pay(&self, cost: &ManaValue, x: u8) -> bool {
+        self.payment(cost, x).is_some()
+    }
+
+    /// Returns the largest X this pool can pay for `cost`, if any
+    pub fn max_x(&self, cost: &ManaValue) -> Option<u8> {
+        if !self.can_pay(cost, 0) {
+            return None;
+        }
+        if cost.x == 0 {
+            return Some(0);
+        }
+        let fixed = pip_count_total(cost) + cost.generic as usize;
+        let spare = self.total() - fixed;
+        Some((spare / cost.x as usize).min(u8::MAX as usize) as u8)
+    }
+}
+
+/// Number of pips of a specific mana type in a cost
+fn pip_count(cost: &ManaValue, mana_type: ManaType) -> usize {
+    let pips = match mana_type {
+        ManaType::White => cost.white,
+        ManaType::Blue => cost.blue,
+        ManaType::Black => cost.black,
+        ManaType::Red => cost.red,
+        ManaType::Green => cost.green,
+        ManaType::Colorless => cost.colorless,
+    };
+    pips as usize
+}
+
+/// Number of colored and {C} pips in a cost
+fn pip_count_total(cost: &ManaValue) -> usize {
+    ManaType::ALL
+        .iter()
+        .map(|&mana_type| pip_count(cost, mana_type))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cost(green: u8, colorless: u8, generic: u8, x: u8) -> ManaValue {
+        ManaValue { white: 0, blue: 0, black: 0, red: 0, green, colorless, generic, x }
+    }
+
+    fn pool(green: usize, red: usize, colorless: usize) -> ManaPool {
+        ManaPool { green, red, colorless, ..ManaPool::default() }
+    }
+
+    #[test]
+    fn pays_pips_with_their_own_type() {
+        assert_eq!(pool(2, 0, 1).payment(&cost(1, 1, 0, 0), 0), Some(pool(1, 0, 1)));
+        assert_eq!(pool(0, 0, 2).payment(&cost(1, 0, 0, 0), 0), None);
+        assert_eq!(pool(2, 0, 0).payment(&cost(0, 1, 0, 0), 0), None);
+    }
+
+    #[test]
+    fn pays_generic_with_colorless_then_the_most_plentiful_color() {
+        assert_eq!(pool(1, 1, 2).payment(&cost(0, 0, 2, 0), 0), Some(pool(0, 0, 2)));
+        assert_eq!(pool(3, 1, 1).payment(&cost(0, 0, 3, 0), 0), Some(pool(2, 0, 1)));
+        assert_eq!(pool(1, 1, 0).payment(&cost(1, 0, 1, 0), 0), Some(pool(1, 1, 0)));
+        assert_eq!(pool(1, 0, 0).payment(&cost(0, 0, 2, 0), 0), None);
+    }
+
+    #[test]
+    fn pays_x_as_generic() {
+        assert_eq!(pool(3, 0, 1).payment(&cost(1, 0, 0, 1), 3), Some(pool(3, 0, 1)));
+        assert_eq!(pool(3, 0, 1).payment(&cost(1, 0, 0, 1), 4), None);
+        assert_eq!(pool(4, 0, 0).payment(&cost(0, 0, 0, 2), 2), Some(pool(4, 0, 0)));
+    }
+
+    #[test]
+    fn finds_the_largest_payable_x() {
+        assert_eq!(pool(3, 0, 1).max_x(&cost(1, 0, 0, 1)), Some(3));
+        assert_eq!(pool(5, 0, 0).max_x(&cost(0, 0, 0, 2)), Some(2));
+        assert_eq!(pool(0, 0, 3).max_x(&cost(1, 0, 0, 1)), None);
+        assert_eq!(pool(2, 0, 0).max_x(&cost(1, 0, 1, 0)), Some(0));
+    }
+
+    #[test]
+    fn removing_a_payment_and_adding_it_back_restores_the_pool() {
+        let original = pool(2, 1, 3);
+        let payment = original.payment(&cost(1, 1, 2, 0), 0).unwrap();
+        let mut remaining = original.clone();
+        remaining.remove(&payment);
+        assert_eq!(remaining.total(), original.total() - 4);
+        remaining.add(&payment);
+        assert_eq!(remaining, original);
+    }
+}
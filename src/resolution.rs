@@ -0,0 +1,1300 @@
+// Stack resolution for Magic: The Gathering Amulet Titan simulation
+
+use crate::abilities::{enter_trigger, is_karoo, Ability, SAGA_FINAL_CHAPTER};
+use crate::cards::{
+    card_type, Card, CardType, HasManaValue, Instant, Land, ManaValue, NonPermanent, Permanent, Sorcery, Spell,
+};
+use crate::game_action::PrimitiveGameAction;
+use crate::mana::ManaType;
+use crate::game_state::*;
+
+// ============================================================================
+// STACK OBJECTS
+// ============================================================================
+
+impl StackObject {
+    /// Returns the effects of this object resolving. The object has already
+    /// been taken off the stack.
+    pub fn resolve(&self, game_state: &GameState) -> Vec<PrimitiveGameAction> {
+        match self {
+            StackObject::Spell { spell, x } => spell.resolve(*x, game_state),
+            StackObject::Trigger(trigger) => trigger.resolve(game_state),
+            StackObject::ActivatedAbility { source, ability, target } => {
+                ability.resolve(*source, target.as_ref(), game_state)
+            }
+        }
+    }
+}
+
+// ============================================================================
+// ACTIVATED ABILITIES
+// ============================================================================
+
+impl Ability {
+    /// Returns the effects of this ability resolving
+    pub fn resolve(
+        &self,
+        source: AbilitySource,
+        target: Option<&Target>,
+        game_state: &GameState,
+    ) -> Vec<PrimitiveGameAction> {
+        // An ability whose target has become illegal doesn't resolve
+        if !game_state.is_legal_target(*self, target) {
+            return Vec::new();
+        }
+        match self {
+            // Legality guarantees the opponent controls the target
+            Ability::DestroyPermanent => match target {
+                Some(&Target::Object(id)) => vec![
+                    PrimitiveGameAction::Destroy(id),
+                    PrimitiveGameAction::SearchForBasicLand(PlayerId::NonActive),
+                ],
+                _ => Vec::new(),
+            },
+            Ability::ReturnPermanentToHand => match target {
+                Some(&Target::Object(id)) => vec![PrimitiveGameAction::ReturnToHand(id)],
+                _ => Vec::new(),
+            },
+            Ability::Transmute { mana_value } => vec![PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                filter: SearchFilter::ManaValue(*mana_value),
+                count: 1,
+                destination: Destination::Hand,
+            })],
+            Ability::ReturnLandsFromGraveyard => game_state
+                .active_player
+                .graveyard
+                .lands
+                .iter()
+                .map(|&land| {
+                    PrimitiveGameAction::ReturnFromGraveyard(GameObject::new(Card::Land(land), TapState::Tapped))
+                })
+                .collect(),
+            Ability::CreateTokenCopy => {
+                let Some(&Target::Object(id)) = target else {
+                    return Vec::new();
+                };
+                let Some(land) = game_state.active_player.battlefield.lands.get(&id) else {
+                    return Vec::new();
+                };
+                // The token copies the land's copiable values, including
+                // anything the land is itself copying
+                let mut copy = GameObject::new(Card::Land(land.permanent), TapState::Untapped);
+                copy.copy_of = land.copy_of;
+                copy.token = true;
+                vec![PrimitiveGameAction::PutOntoBattlefield(copy)]
+            }
+            Ability::SearchLandToTop => vec![PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                filter: SearchFilter::Land,
+                count: 1,
+                destination: Destination::LibraryTop,
+            })],
+            Ability::GrantHaste => match target {
+                Some(&Target::Object(id)) => vec![PrimitiveGameAction::SetHaste(id, true)],
+                _ => Vec::new(),
+            },
+            Ability::CopyArtifact => match (source, target) {
+                (AbilitySource::Battlefield(id), Some(&Target::Object(artifact))) => game_state
+                    .permanent(artifact)
+                    .map(|card| {
+                        vec![PrimitiveGameAction::BecomeCopy {
+                            id,
+                            copy_of: Some(card),
+                            until_end_of_turn: false,
+                        }]
+                    })
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            },
+            Ability::CopyUntilEndOfTurn => match (source, target) {
+                (AbilitySource::Battlefield(id), Some(&Target::Card(card))) => {
+                    vec![PrimitiveGameAction::BecomeCopy {
+                        id,
+                        copy_of: Some(card),
+                        until_end_of_turn: true,
+                    }]
+                }
+                _ => Vec::new(),
+            },
+            Ability::CreateConstruct => {
+                let mut construct = GameObject::new(
+                    Card::Spell(Spell::Permanent(Permanent::ConstructToken)),
+                    TapState::Untapped,
+                );
+                construct.token = true;
+                vec![PrimitiveGameAction::PutOntoBattlefield(construct)]
+            }
+        }
+    }
+}
+
+// ============================================================================
+// SPELLS
+// ============================================================================
+
+impl Spell {
+    /// Returns the effects of this spell resolving with the X it was cast
+    /// for, including moving the card to the battlefield or graveyard
+    pub fn resolve(&self, x: u8, _game_state: &GameState) -> Vec<PrimitiveGameAction> {
+        match self {
+            Spell::Permanent(_) => vec![PrimitiveGameAction::PutOntoBattlefield(GameObject::new(
+                Card::Spell(*self),
+                TapState::Untapped,
+            ))],
+            Spell::NonPermanent(non_permanent) => {
+                let mut effects = match non_permanent {
+                    NonPermanent::Sorcery(Sorcery::Explore) => vec![
+                        PrimitiveGameAction::IncreaseLandPlays(1),
+                        PrimitiveGameAction::DrawCards(1),
+                    ],
+                    NonPermanent::Sorcery(Sorcery::Scapeshift) => {
+                        vec![PrimitiveGameAction::Decide(Decision::Scapeshift)]
+                    }
+                    NonPermanent::Sorcery(Sorcery::GreenSunsZenith) => {
+                        vec![PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                            filter: SearchFilter::GreenCreature { max_mana_value: Some(x as usize) },
+                            count: 1,
+                            destination: Destination::Battlefield(TapState::Untapped),
+                        })]
+                    }
+                    NonPermanent::Instant(Instant::SummonersPact) => vec![
+                        PrimitiveGameAction::DelayTrigger(Trigger::PactPayment),
+                        PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                            filter: SearchFilter::GreenCreature { max_mana_value: None },
+                            count: 1,
+                            destination: Destination::Hand,
+                        }),
+                    ],
+                };
+                // Green Sun's Zenith shuffles itself into the library instead of
+                // going to the graveyard
+                let card = Card::Spell(*self);
+                effects.push(match non_permanent {
+                    NonPermanent::Sorcery(Sorcery::GreenSunsZenith) => PrimitiveGameAction::ShuffleIntoLibrary(card),
+                    _ => PrimitiveGameAction::PutIntoGraveyard(card),
+                });
+                effects
+            }
+        }
+    }
+}
+
+// ============================================================================
+// TRIGGERS
+// ============================================================================
+
+impl Trigger {
+    /// Returns the effects of this trigger resolving
+    pub fn resolve(&self, game_state: &GameState) -> Vec<PrimitiveGameAction> {
+        match self {
+            Trigger::Enters(Card::Land(land)) if is_karoo(*land) => {
+                if game_state.active_player.battlefield.lands.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![PrimitiveGameAction::Decide(Decision::ReturnLandToHand)]
+                }
+            }
+            Trigger::Enters(Card::Spell(Spell::Permanent(Permanent::PrimevalTitan)))
+            | Trigger::Attacks(Card::Spell(Spell::Permanent(Permanent::PrimevalTitan))) => {
+                vec![PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                    filter: SearchFilter::Land,
+                    count: 2,
+                    destination: Destination::Battlefield(TapState::Tapped),
+                })]
+            }
+            Trigger::Enters(Card::Spell(Spell::Permanent(Permanent::Spelunking))) => vec![
+                PrimitiveGameAction::DrawCards(1),
+                PrimitiveGameAction::Decide(Decision::PutLandFromHand {
+                    tap_state: TapState::Untapped,
+                    repeat: false,
+                }),
+            ],
+            Trigger::Enters(Card::Spell(Spell::Permanent(Permanent::AftermathAnalyst))) => {
+                vec![PrimitiveGameAction::MillCards(3)]
+            }
+            Trigger::Enters(Card::Spell(Spell::Permanent(Permanent::ArborealGrazer))) => {
+                vec![PrimitiveGameAction::Decide(Decision::PutLandFromHand {
+                    tap_state: TapState::Tapped,
+                    repeat: false,
+                })]
+            }
+            Trigger::Enters(Card::Spell(Spell::Permanent(Permanent::CultivatorColossus))) => {
+                vec![PrimitiveGameAction::Decide(Decision::PutLandFromHand {
+                    tap_state: TapState::Tapped,
+                    repeat: true,
+                })]
+            }
+            Trigger::Enters(Card::Land(Land::CrumblingVestige)) => {
+                vec![PrimitiveGameAction::Decide(Decision::AddManaOfAnyColor)]
+            }
+            Trigger::Enters(Card::Land(Land::LotusField)) => {
+                vec![PrimitiveGameAction::Decide(Decision::SacrificeUntappedLands { count: 2 })]
+            }
+            Trigger::Enters(_) | Trigger::Attacks(_) => Vec::new(),
+            Trigger::AmuletUntap(id) => {
+                if game_state.active_player.battlefield.tap_state(*id).is_some() {
+                    vec![PrimitiveGameAction::SetTapState(*id, TapState::Untapped)]
+                } else {
+                    Vec::new()
+                }
+            }
+            Trigger::Chapter { source, chapter: SAGA_FINAL_CHAPTER } => {
+                // Chapter III finds a cheap artifact, then the Saga is sacrificed
+                let mut effects = vec![PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                    filter: SearchFilter::Artifact { max_mana_value: 1 },
+                    count: 1,
+                    destination: Destination::Battlefield(TapState::Untapped),
+                })];
+                if game_state.active_player.battlefield.lands.contains_key(source) {
+                    effects.push(PrimitiveGameAction::Sacrifice(*source));
+                }
+                effects
+            }
+            Trigger::Chapter { .. } => Vec::new(),
+            Trigger::EntersAsCopy(source) => {
+                let source = *source;
+                match game_state.active_player.battlefield.lands.get(&source).map(|land| land.permanent) {
+                    Some(Land::Vesuva) => vec![PrimitiveGameAction::Decide(Decision::CopyLand { source })],
+                    Some(Land::EchoingDeeps) => {
+                        vec![PrimitiveGameAction::Decide(Decision::CopyLandFromGraveyard { source })]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            Trigger::PactPayment => {
+                let cost = ManaValue {
+                    green: 2,
+                    generic: 2,
+                    ..ManaValue::default()
+                };
+                match PrimitiveGameAction::pay_mana(game_state, &cost, 0) {
+                    Some(payment) => vec![payment],
+                    None => vec![PrimitiveGameAction::LoseGame(LossReason::UnpaidPact)],
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// DECISIONS
+// ============================================================================
+
+impl Decision {
+    /// Enumerates every legal choice for this decision
+    pub fn options(&self, game_state: &GameState) -> Vec<Choice> {
+        match self {
+            Decision::ReturnLandToHand => {
+                let mut ids: Vec<GameObjectId> =
+                    game_state.active_player.battlefield.lands.keys().copied().collect();
+                ids.sort();
+                ids.into_iter().map(|id| Choice::Objects(vec![id])).collect()
+            }
+            Decision::Scapeshift => {
+                let mut ids: Vec<GameObjectId> =
+                    game_state.active_player.battlefield.lands.keys().copied().collect();
+                ids.sort();
+                let mut subsets: Vec<Vec<GameObjectId>> = vec![Vec::new()];
+                for id in ids {
+                    let with_id: Vec<Vec<GameObjectId>> = subsets
+                        .iter()
+                        .map(|subset| subset.iter().copied().chain([id]).collect())
+                        .collect();
+                    subsets.extend(with_id);
+                }
+                subsets.into_iter().map(Choice::Objects).collect()
+            }
+            Decision::PutLandFromHand { .. } => {
+                let mut lands = game_state.active_player.hand.lands.clone();
+                lands.sort_by_key(|&land| land as usize);
+                lands.dedup();
+                let mut choices: Vec<Choice> =
+                    lands.into_iter().map(|land| Choice::Cards(vec![Card::Land(land)])).collect();
+                choices.push(Choice::Cards(Vec::new()));
+                choices
+            }
+            Decision::SearchLibrary { filter, count, .. } => {
+                let library = &game_state.active_player.library;
+                let candidates: Vec<(Card, usize)> = library
+                    .cards
+                    .iter()
+                    .map(|(card, _)| (card, library.count(card)))
+                    .filter(|&(card, copies)| copies > 0 && filter.matches(card))
+                    .collect();
+                let mut selections = card_selections(&candidates, *count);
+                // Finding the most cards is almost always best, so offer it first
+                selections.sort_by_key(|cards| std::cmp::Reverse(cards.len()));
+                selections.into_iter().map(Choice::Cards).collect()
+            }
+            Decision::CopyLand { source } => {
+                // One option per distinct land to copy, plus copying nothing
+                let mut choices = Vec::new();
+                let mut copied: Vec<Card> = Vec::new();
+                for (id, card) in copyable_lands(game_state, *source) {
+                    if !copied.contains(&card) {
+                        copied.push(card);
+                        choices.push(Choice::Objects(vec![id]));
+                    }
+                }
+                choices.push(Choice::Objects(Vec::new()));
+                choices
+            }
+            Decision::CopyLandFromGraveyard { .. } => {
+                let mut choices = Vec::new();
+                for land in graveyard_lands(game_state) {
+                    let choice = Choice::Cards(vec![Card::Land(land)]);
+                    if !choices.contains(&choice) {
+                        choices.push(choice);
+                    }
+                }
+                choices.push(Choice::Cards(Vec::new()));
+                choices
+            }
+            Decision::AddManaOfAnyColor => ManaType::COLORS.into_iter().map(Choice::Color).collect(),
+            Decision::SacrificeUntappedLands { count } => {
+                // Untapped lands that are the same card are interchangeable, so
+                // offer each distinct selection once
+                let lands = untapped_lands(game_state);
+                let mut candidates: Vec<(Card, usize)> = Vec::new();
+                for &(_, card) in &lands {
+                    match candidates.iter_mut().find(|(c, _)| *c == card) {
+                        Some((_, copies)) => *copies += 1,
+                        None => candidates.push((card, 1)),
+                    }
+                }
+                let required = (*count).min(lands.len());
+                card_selections(&candidates, required)
+                    .into_iter()
+                    .filter(|selection| selection.len() == required)
+                    .map(|selection| {
+                        let mut ids: Vec<GameObjectId> = Vec::new();
+                        for card in selection {
+                            let id = lands.iter().find(|&&(id, c)| c == card && !ids.contains(&id));
+                            ids.extend(id.map(|&(id, _)| id));
+                        }
+                        Choice::Objects(ids)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the effects of answering this decision with `choice`. Parts of
+    /// the choice that aren't legal are ignored.
+    pub fn resolve(&self, choice: &Choice, game_state: &GameState) -> Vec<PrimitiveGameAction> {
+        match (self, choice) {
+            (Decision::ReturnLandToHand, Choice::Objects(ids)) => ids
+                .iter()
+                .find(|id| game_state.active_player.battlefield.lands.contains_key(id))
+                .map(|&id| vec![PrimitiveGameAction::ReturnToHand(id)])
+                .unwrap_or_default(),
+            (Decision::Scapeshift, Choice::Objects(ids)) => {
+                let mut sacrificed: Vec<GameObjectId> = Vec::new();
+                for &id in ids {
+                    if game_state.active_player.battlefield.lands.contains_key(&id) && !sacrificed.contains(&id) {
+                        sacrificed.push(id);
+                    }
+                }
+                let count = sacrificed.len();
+                let mut effects: Vec<PrimitiveGameAction> =
+                    sacrificed.into_iter().map(PrimitiveGameAction::Sacrifice).collect();
+                if count > 0 {
+                    effects.push(PrimitiveGameAction::Decide(Decision::SearchLibrary {
+                        filter: SearchFilter::Land,
+                        count,
+                        destination: Destination::Battlefield(TapState::Tapped),
+                    }));
+                }
+                effects
+            }
+            (Decision::PutLandFromHand { tap_state, repeat }, Choice::Cards(cards)) => {
+                let land = cards.iter().find_map(|&card| match card {
+                    Card::Land(land) if game_state.active_player.hand.lands.contains(&land) => Some(land),
+                    _ => None,
+                });
+                let Some(land) = land else {
+                    return Vec::new();
+                };
+                let mut effects = vec![PrimitiveGameAction::PlayLand(land, *tap_state)];
+                if *repeat {
+                    effects.push(PrimitiveGameAction::DrawCards(1));
+                    effects.push(PrimitiveGameAction::Decide(self.clone()));
+                }
+                effects
+            }
+            (Decision::SearchLibrary { filter, count, destination }, Choice::Cards(cards)) => {
+                // Only take cards that match and are actually left in the library
+                let library = &game_state.active_player.library;
+                let mut found: Vec<Card> = Vec::new();
+                for &card in cards {
+                    let taken = found.iter().filter(|&&c| c == card).count();
+                    if found.len() < *count && filter.matches(card) && library.count(card) > taken {
+                        found.push(card);
+                    }
+                }
+                if found.is_empty() {
+                    return Vec::new();
+                }
+                match destination {
+                    Destination::Hand => vec![PrimitiveGameAction::SearchLibraryToHand(found)],
+                    Destination::LibraryTop => vec![PrimitiveGameAction::SearchLibraryToTop(found)],
+                    Destination::Battlefield(tap_state) => {
+                        vec![PrimitiveGameAction::SearchLibraryToBattlefield(
+                            found.into_iter().map(|card| GameObject::new(card, *tap_state)).collect(),
+                        )]
+                    }
+                }
+            }
+            (Decision::CopyLand { source }, Choice::Objects(ids)) => {
+                let copyable = copyable_lands(game_state, *source);
+                let Some(&(_, card)) = copyable.iter().find(|(id, _)| ids.contains(id)) else {
+                    return Vec::new();
+                };
+                enter_as_copy(game_state, *source, card)
+            }
+            (Decision::CopyLandFromGraveyard { source }, Choice::Cards(cards)) => {
+                let lands = graveyard_lands(game_state);
+                let copied = cards.iter().find_map(|&card| match card {
+                    Card::Land(land) if lands.contains(&land) => Some(card),
+                    _ => None,
+                });
+                let Some(card) = copied else {
+                    return Vec::new();
+                };
+                enter_as_copy(game_state, *source, card)
+            }
+            (Decision::SacrificeUntappedLands { count }, Choice::Objects(ids)) => {
+                // Take the chosen untapped lands, then make up any shortfall in
+                // id order since the sacrifice isn't optional
+                let lands: Vec<GameObjectId> = untapped_lands(game_state).into_iter().map(|(id, _)| id).collect();
+                let mut sacrificed: Vec<GameObjectId> = Vec::new();
+                for &id in ids.iter().chain(&lands) {
+                    if sacrificed.len() < *count && lands.contains(&id) && !sacrificed.contains(&id) {
+                        sacrificed.push(id);
+                    }
+                }
+                sacrificed.into_iter().map(PrimitiveGameAction::Sacrifice).collect()
+            }
+            (Decision::AddManaOfAnyColor, Choice::Color(color)) if ManaType::COLORS.contains(color) => {
+                vec![PrimitiveGameAction::AddMana(ManaPool::of(*color, 1))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Returns the effects of Vesuva or Echoing Deeps entering as a copy of a
+/// land: it becomes the copy, enters tapped after all (triggering Amulet of
+/// Vigor) and gets the copied land's enter trigger
+fn enter_as_copy(game_state: &GameState, source: GameObjectId, card: Card) -> Vec<PrimitiveGameAction> {
+    let mut effects = vec![PrimitiveGameAction::BecomeCopy {
+        id: source,
+        copy_of: Some(card),
+        until_end_of_turn: false,
+    }];
+    let tapped = game_state.entering_tap_state(card, TapState::Tapped) == TapState::Tapped;
+    if tapped {
+        effects.push(PrimitiveGameAction::SetTapState(source, TapState::Tapped));
+    }
+    effects.extend(enter_trigger(card).map(PrimitiveGameAction::Trigger));
+    if tapped {
+        let untaps = game_state.amulets().into_iter().map(|_| Trigger::AmuletUntap(source));
+        effects.extend(untaps.map(PrimitiveGameAction::Trigger));
+    }
+    effects
+}
+
+/// Returns the active player's untapped lands with the card each currently
+/// is, in id order
+fn untapped_lands(game_state: &GameState) -> Vec<(GameObjectId, Card)> {
+    let mut lands: Vec<(GameObjectId, Card)> = game_state
+        .active_player
+        .battlefield
+        .lands
+        .iter()
+        .filter(|(_, land)| land.tap_state == TapState::Untapped)
+        .map(|(&id, land)| (id, land.card()))
+        .collect();
+    lands.sort_by_key(|&(id, _)| id);
+    lands
+}
+
+/// Returns the land cards in every player's graveyard, sorted with duplicates
+fn graveyard_lands(game_state: &GameState) -> Vec<Land> {
+    let players = [Some(&game_state.active_player), game_state.non_active_player.as_ref()];
+    let mut lands: Vec<Land> = players
+        .into_iter()
+        .flatten()
+        .flat_map(|player| player.graveyard.lands.iter().copied())
+        .collect();
+    lands.sort_by_key(|&land| land as usize);
+    lands
+}
+
+/// Returns every land on either battlefield other than `source`, with the
+/// card it currently is, in id order
+fn copyable_lands(game_state: &GameState, source: GameObjectId) -> Vec<(GameObjectId, Card)> {
+    let players = [Some(&game_state.active_player), game_state.non_active_player.as_ref()];
+    let mut lands: Vec<(GameObjectId, Card)> = players
+        .into_iter()
+        .flatten()
+        .flat_map(|player| player.battlefield.lands.iter())
+        .filter(|&(&id, land)| id != source && matches!(land.card(), Card::Land(_)))
+        .map(|(&id, land)| (id, land.card()))
+        .collect();
+    lands.sort_by_key(|&(id, _)| id);
+    lands
+}
+
+impl SearchFilter {
+    /// Returns true if a library search with this filter can find `card`
+    pub fn matches(&self, card: Card) -> bool {
+        match self {
+            SearchFilter::Land => matches!(card, Card::Land(_)),
+            SearchFilter::GreenCreature { max_mana_value } => match card {
+                Card::Spell(spell) => {
+                    let cost = spell.mana_value();
+                    card_type(card).contains(CardType::CREATURE)
+                        && cost.green > 0
+                        && max_mana_value.is_none_or(|max| cost.converted() <= max)
+                }
+                Card::Land(_) => false,
+            },
+            SearchFilter::Artifact { max_mana_value } => match card {
+                Card::Spell(spell) => {
+                    card_type(card).contains(CardType::ARTIFACT)
+                        && spell.mana_value().converted() <= *max_mana_value
+                }
+                Card::Land(_) => false,
+            },
+            SearchFilter::ManaValue(mana_value) => card.mana_value().converted() == *mana_value,
+        }
+    }
+}
+
+/// Enumerates every multiset of at most `count` cards drawn from `candidates`,
+/// where each candidate is a card and the number of copies available
+fn card_selections(candidates: &[(Card, usize)], count: usize) -> Vec<Vec<Card>> {
+    let Some((&(card, copies), rest)) = candidates.split_first() else {
+        return vec![Vec::new()];
+    };
+    let mut selections = Vec::new();
+    for taken in 0..=copies.min(count) {
+        for mut selection in card_selections(rest, count - taken) {
+            selection.extend(std::iter::repeat_n(card, taken));
+            selections.push(selection);
+        }
+    }
+    selections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::Land;
+    use crate::mana::ManaType;
+    use crate::game_action::GameAction;
+
+    #[test]
+    fn karoo_bounces_a_land_and_reverts() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let forest = game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.active_player.hand.lands.push(Land::GruulTurf);
+        let before = game_state.snapshot();
+
+        let actions = [
+            GameAction::Primitive(PrimitiveGameAction::PlayLand(Land::GruulTurf, TapState::Untapped)),
+            GameAction::Primitive(PrimitiveGameAction::ResolveTop),
+            GameAction::Choose(Choice::Objects(vec![forest])),
+        ];
+        let results: Vec<_> = actions.iter().map(|action| action.apply(&mut game_state)).collect();
+
+        let battlefield = &game_state.active_player.battlefield.lands;
+        assert_eq!(battlefield.len(), 1);
+        assert!(battlefield
+            .values()
+            .all(|land| land.permanent == Land::GruulTurf && land.tap_state == TapState::Tapped));
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::Forest]);
+        assert!(game_state.stack.objects.is_empty());
+        assert_eq!(game_state.decision, None);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn each_amulet_untaps_a_karoo_before_it_bounces() {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.add_test_permanent(Permanent::AmuletOfVigor);
+        game_state.add_test_permanent(Permanent::AmuletOfVigor);
+        game_state.active_player.hand.lands.push(Land::GruulTurf);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::Primitive(PrimitiveGameAction::PlayLand(
+            Land::GruulTurf,
+            TapState::Untapped,
+        ))
+        .apply(&mut game_state)];
+        let turf = *game_state.active_player.battlefield.lands.keys().next().unwrap();
+        assert_eq!(game_state.stack.objects.len(), 3);
+        for _ in 0..2 {
+            results.push(GameAction::Primitive(PrimitiveGameAction::ResolveTop).apply(&mut game_state));
+            let ability = game_state.available_mana_abilities(turf).pop().unwrap();
+            results.push(GameAction::ActivateManaAbility { source: turf, ability }.apply(&mut game_state));
+        }
+        results.push(GameAction::Primitive(PrimitiveGameAction::ResolveTop).apply(&mut game_state));
+        results.push(GameAction::Choose(Choice::Objects(vec![turf])).apply(&mut game_state));
+
+        assert_eq!(game_state.active_player.mana_pool.red, 2);
+        assert_eq!(game_state.active_player.mana_pool.green, 2);
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::GruulTurf]);
+        assert!(game_state.active_player.battlefield.lands.is_empty());
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn bouncing_only_offers_lands_on_the_battlefield() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let turf = game_state.add_test_land(Land::GruulTurf, TapState::Tapped);
+        assert_eq!(
+            Decision::ReturnLandToHand.options(&game_state),
+            vec![Choice::Objects(vec![turf])]
+        );
+        let missing = game_state.next_game_object_id();
+        assert!(Decision::ReturnLandToHand
+            .resolve(&Choice::Objects(vec![missing]), &game_state)
+            .is_empty());
+    }
+
+    #[test]
+    fn titan_searches_for_two_lands_that_enter_tapped() {
+        let library = vec![Card::Land(Land::Forest), Card::Land(Land::Forest), Card::Land(Land::GruulTurf)];
+        let mut game_state = GameState::for_test(library);
+        game_state.stack.objects.push(StackObject::Trigger(Trigger::Enters(Card::Spell(Spell::Permanent(
+            Permanent::PrimevalTitan,
+        )))));
+        let before = game_state.snapshot();
+
+        let resolve = GameAction::Primitive(PrimitiveGameAction::ResolveTop).apply(&mut game_state);
+        let decision = game_state.decision.clone().unwrap();
+        let options = decision.options(&game_state);
+        assert_eq!(options.len(), 5);
+        assert_eq!(options[0], Choice::Cards(vec![Card::Land(Land::GruulTurf), Card::Land(Land::Forest)]));
+
+        let choice = Choice::Cards(vec![Card::Land(Land::Forest); 3]);
+        let search = GameAction::Choose(choice).apply(&mut game_state);
+        let lands = &game_state.active_player.battlefield.lands;
+        assert_eq!(lands.len(), 2);
+        assert!(lands.values().all(|land| land.permanent == Land::Forest && land.tap_state == TapState::Tapped));
+        assert_eq!(game_state.active_player.library.len(), 1);
+
+        search.revert(&mut game_state);
+        resolve.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn scapeshift_sacrifices_lands_to_search_for_as_many() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::Forest); 3]);
+        let first = game_state.add_test_land(Land::Forest, TapState::Tapped);
+        let second = game_state.add_test_land(Land::Forest, TapState::Untapped);
+        let scapeshift = Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::Scapeshift));
+        game_state.stack.objects.push(StackObject::Spell { spell: scapeshift, x: 0 });
+        let before = game_state.snapshot();
+
+        let resolve = GameAction::Primitive(PrimitiveGameAction::ResolveTop).apply(&mut game_state);
+        assert_eq!(game_state.active_player.graveyard.spells, vec![scapeshift]);
+        assert_eq!(Decision::Scapeshift.options(&game_state).len(), 4);
+
+        let sacrifice = GameAction::Choose(Choice::Objects(vec![second, first, second])).apply(&mut game_state);
+        assert!(game_state.active_player.battlefield.lands.is_empty());
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::Forest; 2]);
+        assert_eq!(
+            game_state.decision,
+            Some(Decision::SearchLibrary {
+                filter: SearchFilter::Land,
+                count: 2,
+                destination: Destination::Battlefield(TapState::Tapped),
+            })
+        );
+
+        sacrifice.revert(&mut game_state);
+        resolve.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn summoners_pact_finds_a_green_creature_and_must_be_paid_for() {
+        let library = vec![
+            Card::Spell(Spell::Permanent(Permanent::PrimevalTitan)),
+            Card::Spell(Spell::Permanent(Permanent::AmuletOfVigor)),
+            Card::Land(Land::Forest),
+        ];
+        let mut game_state = GameState::for_test(library);
+        let pact = Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact));
+        game_state.stack.objects.push(StackObject::Spell { spell: pact, x: 0 });
+        GameAction::Primitive(PrimitiveGameAction::ResolveTop).apply(&mut game_state);
+        assert_eq!(game_state.delayed_triggers, vec![Trigger::PactPayment]);
+
+        let titan = Card::Spell(Spell::Permanent(Permanent::PrimevalTitan));
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options, vec![Choice::Cards(vec![titan]), Choice::Cards(Vec::new())]);
+        GameAction::Choose(Choice::Cards(vec![titan])).apply(&mut game_state);
+        assert_eq!(game_state.active_player.hand.spells, vec![Spell::Permanent(Permanent::PrimevalTitan)]);
+
+        while game_state.turn.step != Step::Upkeep {
+            GameAction::PassPriority.apply(&mut game_state);
+        }
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::PactPayment)]);
+        assert!(game_state.delayed_triggers.is_empty());
+        let upkeep = game_state.snapshot();
+
+        let unpaid = GameAction::PassPriority.apply(&mut game_state);
+        assert_eq!(game_state.outcome, Some(GameOutcome::Lost(LossReason::UnpaidPact)));
+        unpaid.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), upkeep);
+
+        game_state.active_player.mana_pool = ManaPool { green: 3, colorless: 1, ..ManaPool::default() };
+        GameAction::PassPriority.apply(&mut game_state);
+        assert_eq!(game_state.outcome, None);
+        assert!(game_state.active_player.mana_pool.is_empty());
+    }
+
+    #[test]
+    fn green_suns_zenith_pays_x_and_finds_a_creature_costing_at_most_x() {
+        let library = vec![
+            Card::Spell(Spell::Permanent(Permanent::PrimevalTitan)),
+            Card::Spell(Spell::Permanent(Permanent::ArborealGrazer)),
+        ];
+        let mut game_state = GameState::for_test(library);
+        game_state.active_player.mana_pool = ManaPool { green: 3, colorless: 1, ..ManaPool::default() };
+        let zenith = Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::GreenSunsZenith));
+        game_state.active_player.hand.spells.push(zenith);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::CastSpell { spell: zenith, x: 3 }.apply(&mut game_state)];
+        assert!(game_state.active_player.mana_pool.is_empty());
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        let grazer = Card::Spell(Spell::Permanent(Permanent::ArborealGrazer));
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options, vec![Choice::Cards(vec![grazer]), Choice::Cards(Vec::new())]);
+        assert_eq!(game_state.active_player.library.count(Card::Spell(zenith)), 1);
+
+        let titan = Card::Spell(Spell::Permanent(Permanent::PrimevalTitan));
+        let too_big = GameAction::Choose(Choice::Cards(vec![titan])).apply(&mut game_state);
+        assert!(game_state.active_player.battlefield.non_lands.is_empty());
+        too_big.revert(&mut game_state);
+        results.push(GameAction::Choose(Choice::Cards(vec![grazer])).apply(&mut game_state));
+        assert_eq!(game_state.active_player.battlefield.non_lands.len(), 1);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn explore_gives_an_extra_land_play_and_draws() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::Forest)]);
+        let explore = Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::Explore));
+        game_state.stack.objects.push(StackObject::Spell { spell: explore, x: 0 });
+        let before = game_state.snapshot();
+
+        let result = GameAction::PassPriority.apply(&mut game_state);
+        assert_eq!(game_state.active_player.battlefield.land_plays, 2);
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::Forest]);
+        assert_eq!(game_state.active_player.graveyard.spells, vec![explore]);
+
+        result.revert(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn urzas_saga_makes_constructs_and_sacrifices_itself_after_chapter_three() {
+        let amulet = Card::Spell(Spell::Permanent(Permanent::AmuletOfVigor));
+        let mut game_state = GameState::for_test(vec![amulet; 2]);
+        let saga = game_state.add_test_land(Land::UrzasSaga, TapState::Untapped);
+        game_state.active_player.battlefield.lands.get_mut(&saga).unwrap().lore_counters = 2;
+        let before = game_state.snapshot();
+
+        let activate = GameAction::ActivateAbility { source: AbilitySource::Battlefield(saga), target: None };
+        activate.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+
+        game_state.active_player.mana_pool = ManaPool { colorless: 2, ..ManaPool::default() };
+        let with_mana = game_state.snapshot();
+        let results = [activate.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        assert_eq!(game_state.active_player.battlefield.tap_state(saga), Some(TapState::Tapped));
+        let construct = game_state.active_player.battlefield.non_lands.values().next().unwrap();
+        assert_eq!(construct.permanent, Permanent::ConstructToken);
+        assert!(construct.token);
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), with_mana);
+
+        while game_state.stack.objects.is_empty() {
+            GameAction::PassPriority.apply(&mut game_state);
+        }
+        assert_eq!(game_state.turn.step, Step::PrecombatMain);
+        assert_eq!(game_state.active_player.battlefield.lands[&saga].lore_counters, 3);
+        GameAction::PassPriority.apply(&mut game_state);
+        assert!(game_state.active_player.battlefield.lands.is_empty());
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::UrzasSaga]);
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options, vec![Choice::Cards(vec![amulet]), Choice::Cards(Vec::new())]);
+    }
+
+    #[test]
+    fn spelunking_draws_and_puts_a_land_onto_the_battlefield_untapped() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::GruulTurf)]);
+        game_state.add_test_land(Land::Forest, TapState::Untapped);
+        let spelunking = Spell::Permanent(Permanent::Spelunking);
+        game_state.stack.objects.push(StackObject::Spell { spell: spelunking, x: 0 });
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PassPriority.apply(&mut game_state)];
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::GruulTurf]);
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options, vec![Choice::Cards(vec![Card::Land(Land::GruulTurf)]), Choice::Cards(Vec::new())]);
+
+        results.push(GameAction::Choose(options[0].clone()).apply(&mut game_state));
+        let turf = game_state.active_player.battlefield.lands.values().find(|land| land.permanent == Land::GruulTurf);
+        assert_eq!(turf.map(|turf| turf.tap_state), Some(TapState::Untapped));
+        assert_eq!(game_state.active_player.battlefield.land_plays, 1);
+        assert_eq!(game_state.stack.objects.len(), 1);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn cultivator_colossus_keeps_putting_lands_and_drawing() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::Forest)]);
+        game_state.active_player.hand.lands = vec![Land::Forest, Land::Forest];
+        let colossus = Card::Spell(Spell::Permanent(Permanent::CultivatorColossus));
+        game_state.stack.objects.push(StackObject::Trigger(Trigger::Enters(colossus)));
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PassPriority.apply(&mut game_state)];
+        let forest = Choice::Cards(vec![Card::Land(Land::Forest)]);
+        for put in 1..=3 {
+            assert!(game_state.decision.is_some());
+            results.push(GameAction::Choose(forest.clone()).apply(&mut game_state));
+            assert_eq!(game_state.active_player.battlefield.lands.len(), put);
+        }
+        assert!(game_state.active_player.hand.lands.is_empty());
+        assert!(game_state.active_player.library.is_empty());
+        assert!(game_state
+            .active_player
+            .battlefield
+            .lands
+            .values()
+            .all(|land| land.tap_state == TapState::Tapped));
+        results.push(GameAction::Choose(Choice::Cards(Vec::new())).apply(&mut game_state));
+        assert_eq!(game_state.decision, None);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn arboreal_grazer_puts_one_land_onto_the_battlefield_tapped() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::Forest)]);
+        game_state.active_player.hand.lands = vec![Land::Forest, Land::Forest];
+        let grazer = Card::Spell(Spell::Permanent(Permanent::ArborealGrazer));
+        game_state.stack.objects.push(StackObject::Trigger(Trigger::Enters(grazer)));
+        GameAction::PassPriority.apply(&mut game_state);
+        GameAction::Choose(Choice::Cards(vec![Card::Land(Land::Forest)])).apply(&mut game_state);
+        assert_eq!(game_state.decision, None);
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::Forest]);
+        assert!(game_state
+            .active_player
+            .battlefield
+            .lands
+            .values()
+            .all(|land| land.tap_state == TapState::Tapped));
+    }
+
+    #[test]
+    fn aftermath_analyst_mills_and_returns_lands_from_the_graveyard() {
+        let mut game_state = GameState::for_test(vec![Card::Land(Land::Forest); 3]);
+        let analyst = Card::Spell(Spell::Permanent(Permanent::AftermathAnalyst));
+        game_state.stack.objects.push(StackObject::Trigger(Trigger::Enters(analyst)));
+        GameAction::PassPriority.apply(&mut game_state);
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::Forest; 3]);
+        assert!(game_state.active_player.library.is_empty());
+
+        game_state.active_player.graveyard.lands.insert(1, Land::GruulTurf);
+        let analyst = game_state.add_test_permanent(Permanent::AftermathAnalyst);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Green, 4);
+        let before = game_state.snapshot();
+
+        let results = [
+            GameAction::ActivateAbility { source: AbilitySource::Battlefield(analyst), target: None }.apply(&mut game_state),
+            GameAction::PassPriority.apply(&mut game_state),
+        ];
+        assert!(game_state.active_player.battlefield.non_lands.is_empty());
+        assert!(game_state.active_player.mana_pool.is_empty());
+        assert!(game_state.active_player.graveyard.lands.is_empty());
+        assert_eq!(game_state.active_player.graveyard.spells, vec![Spell::Permanent(Permanent::AftermathAnalyst)]);
+        let lands = &game_state.active_player.battlefield.lands;
+        assert_eq!(lands.len(), 4);
+        assert!(lands.values().all(|land| land.tap_state == TapState::Tapped));
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::Enters(Card::Land(Land::GruulTurf)))]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn boseiju_channel_destroys_a_nonbasic_land_and_the_opponent_finds_a_basic() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let mut opponent = game_state.active_player.clone();
+        opponent.library = Library::new(vec![Card::Land(Land::Forest)], 0);
+        let vesuva = game_state.next_game_object_id();
+        opponent.battlefield.lands.insert(vesuva, GameObject::new(Land::Vesuva, TapState::Untapped));
+        game_state.non_active_player = Some(opponent);
+        let forest = game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.active_player.hand.lands.push(Land::BoseijuWhoEndures);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Green, 2);
+        let before = game_state.snapshot();
+
+        let boseiju = AbilitySource::Hand(Card::Land(Land::BoseijuWhoEndures));
+        let own_forest = GameAction::ActivateAbility { source: boseiju, target: Some(Target::Object(forest)) };
+        own_forest.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), before);
+
+        let channel = GameAction::ActivateAbility { source: boseiju, target: Some(Target::Object(vesuva)) };
+        let mut results = vec![channel.apply(&mut game_state)];
+        assert!(game_state.active_player.hand.lands.is_empty());
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::BoseijuWhoEndures]);
+        assert!(game_state.active_player.mana_pool.is_empty());
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+
+        let opponent = game_state.non_active_player.as_ref().unwrap();
+        assert_eq!(opponent.graveyard.lands, vec![Land::Vesuva]);
+        assert!(opponent.battlefield.lands.values().all(|land| land.permanent == Land::Forest));
+        assert!(opponent.library.is_empty());
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn otawara_channel_returns_a_creature_to_hand() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let titan = game_state.add_test_permanent(Permanent::PrimevalTitan);
+        game_state.active_player.hand.lands.push(Land::OtawaraSoaringCity);
+        game_state.active_player.mana_pool = ManaPool { blue: 1, colorless: 3, ..ManaPool::default() };
+        let otawara = AbilitySource::Hand(Card::Land(Land::OtawaraSoaringCity));
+        GameAction::ActivateAbility { source: otawara, target: Some(Target::Object(titan)) }.apply(&mut game_state);
+        GameAction::PassPriority.apply(&mut game_state);
+        assert!(game_state.active_player.battlefield.non_lands.is_empty());
+        assert_eq!(game_state.active_player.hand.spells, vec![Spell::Permanent(Permanent::PrimevalTitan)]);
+    }
+
+    #[test]
+    fn tolaria_west_transmutes_for_a_land_at_sorcery_speed() {
+        let library = vec![
+            Card::Spell(Spell::Permanent(Permanent::AmuletOfVigor)),
+            Card::Land(Land::Forest),
+            Card::Land(Land::GruulTurf),
+        ];
+        let mut game_state = GameState::for_test(library);
+        game_state.active_player.hand.lands.push(Land::TolariaWest);
+        game_state.active_player.mana_pool = ManaPool { blue: 2, colorless: 1, ..ManaPool::default() };
+        let transmute = GameAction::ActivateAbility {
+            source: AbilitySource::Hand(Card::Land(Land::TolariaWest)),
+            target: None,
+        };
+
+        game_state.turn.step = Step::Combat;
+        let in_combat = game_state.snapshot();
+        transmute.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), in_combat);
+
+        game_state.turn.step = Step::PrecombatMain;
+        let before = game_state.snapshot();
+        let mut results = vec![transmute.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options.len(), 3);
+        assert!(!options.contains(&Choice::Cards(vec![Card::Spell(Spell::Permanent(Permanent::AmuletOfVigor))])));
+        results.push(GameAction::Choose(Choice::Cards(vec![Card::Land(Land::GruulTurf)])).apply(&mut game_state));
+        assert_eq!(game_state.active_player.hand.lands, vec![Land::GruulTurf]);
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::TolariaWest]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn vesuva_enters_tapped_as_a_copy_and_untapped_otherwise() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let forest = game_state.add_test_land(Land::Forest, TapState::Tapped);
+        game_state.add_test_permanent(Permanent::AmuletOfVigor);
+        game_state.active_player.hand.lands.push(Land::Vesuva);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PlayLand(Land::Vesuva).apply(&mut game_state)];
+        let vesuva = *game_state.active_player.battlefield.lands.keys().max().unwrap();
+        assert_eq!(game_state.active_player.battlefield.tap_state(vesuva), Some(TapState::Untapped));
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::EntersAsCopy(vesuva))]);
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options, vec![Choice::Objects(vec![forest]), Choice::Objects(Vec::new())]);
+
+        let decline = GameAction::Choose(Choice::Objects(Vec::new())).apply(&mut game_state);
+        assert_eq!(game_state.active_player.battlefield.lands[&vesuva].card(), Card::Land(Land::Vesuva));
+        assert_eq!(game_state.active_player.battlefield.tap_state(vesuva), Some(TapState::Untapped));
+        decline.revert(&mut game_state);
+
+        results.push(GameAction::Choose(Choice::Objects(vec![forest])).apply(&mut game_state));
+        assert_eq!(game_state.active_player.battlefield.lands[&vesuva].card(), Card::Land(Land::Forest));
+        assert_eq!(game_state.active_player.battlefield.tap_state(vesuva), Some(TapState::Tapped));
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::AmuletUntap(vesuva))]);
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        let ability = game_state.available_mana_abilities(vesuva).pop().unwrap();
+        results.push(GameAction::ActivateManaAbility { source: vesuva, ability }.apply(&mut game_state));
+        assert_eq!(game_state.active_player.mana_pool, ManaPool::of(ManaType::Green, 1));
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn mirrorpool_makes_a_token_copy_of_a_land_for_four() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let mirrorpool = game_state.add_test_land(Land::Mirrorpool, TapState::Untapped);
+        let vesuva = game_state.add_test_land(Land::Vesuva, TapState::Untapped);
+        game_state.active_player.battlefield.set_copy_of(vesuva, Some(Card::Land(Land::GruulTurf)), false);
+        let copy = GameAction::ActivateAbility {
+            source: AbilitySource::Battlefield(mirrorpool),
+            target: Some(Target::Object(vesuva)),
+        };
+
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Colorless, 3);
+        let short = game_state.snapshot();
+        copy.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), short);
+
+        game_state.active_player.mana_pool = ManaPool { green: 3, colorless: 1, ..ManaPool::default() };
+        let before = game_state.snapshot();
+        let results = [copy.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::Mirrorpool]);
+        let token = game_state.active_player.battlefield.lands.values().find(|land| land.token).unwrap();
+        assert_eq!(token.card(), Card::Land(Land::GruulTurf));
+        assert_eq!(token.tap_state, TapState::Tapped);
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::Enters(Card::Land(Land::GruulTurf)))]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn echoing_deeps_copies_a_land_from_a_graveyard_and_enters_tapped() {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.active_player.graveyard.add_card(Card::Land(Land::GruulTurf));
+        game_state.active_player.hand.lands.push(Land::EchoingDeeps);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PlayLand(Land::EchoingDeeps).apply(&mut game_state)];
+        let deeps = *game_state.active_player.battlefield.lands.keys().max().unwrap();
+        assert_eq!(game_state.active_player.battlefield.tap_state(deeps), Some(TapState::Untapped));
+        assert!(game_state.available_mana_abilities(deeps).is_empty());
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        let options = game_state.decision.clone().unwrap().options(&game_state);
+        assert_eq!(options, vec![Choice::Cards(vec![Card::Land(Land::GruulTurf)]), Choice::Cards(Vec::new())]);
+
+        results.push(GameAction::Choose(Choice::Cards(vec![Card::Land(Land::GruulTurf)])).apply(&mut game_state));
+        assert_eq!(game_state.active_player.battlefield.lands[&deeps].card(), Card::Land(Land::GruulTurf));
+        assert_eq!(game_state.active_player.battlefield.tap_state(deeps), Some(TapState::Tapped));
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::Enters(Card::Land(Land::GruulTurf)))]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn urzas_cave_puts_a_land_on_top_and_reverting_restores_the_top_cards() {
+        let mut game_state = GameState::for_test(Vec::new());
+        for land in [Land::Forest, Land::GruulTurf, Land::Forest] {
+            game_state.active_player.library.put_on_top(Card::Land(land));
+        }
+        let cave = game_state.add_test_land(Land::UrzasCave, TapState::Untapped);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Colorless, 3);
+        let before = game_state.snapshot();
+
+        let search = GameAction::ActivateAbility { source: AbilitySource::Battlefield(cave), target: None };
+        let mut results = vec![search.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        results.push(GameAction::Choose(Choice::Cards(vec![Card::Land(Land::Forest)])).apply(&mut game_state));
+        assert_eq!(
+            game_state.active_player.library.top,
+            vec![Card::Land(Land::GruulTurf), Card::Land(Land::Forest), Card::Land(Land::Forest)]
+        );
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::UrzasCave]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn hanweir_battlements_gives_a_new_creature_haste_for_two_red_and_a_green() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let titan = game_state.add_test_permanent(Permanent::PrimevalTitan);
+        game_state.active_player.battlefield.non_lands.get_mut(&titan).unwrap().entered_turn = game_state.turn.number;
+        let hanweir = game_state.add_test_land(Land::HanweirBattlements, TapState::Untapped);
+        let haste = GameAction::ActivateAbility {
+            source: AbilitySource::Battlefield(hanweir),
+            target: Some(Target::Object(titan)),
+        };
+
+        game_state.active_player.mana_pool = ManaPool { red: 1, green: 2, ..ManaPool::default() };
+        let short = game_state.snapshot();
+        haste.apply(&mut game_state);
+        assert_eq!(game_state.snapshot(), short);
+
+        game_state.active_player.mana_pool = ManaPool { red: 2, green: 1, ..ManaPool::default() };
+        let before = game_state.snapshot();
+        let mut results = vec![haste.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        assert!(game_state.active_player.battlefield.non_lands[&titan].haste);
+        game_state.turn.step = Step::Combat;
+        results.push(GameAction::DeclareAttackers(vec![titan]).apply(&mut game_state));
+        assert_eq!(game_state.active_player.battlefield.tap_state(titan), Some(TapState::Tapped));
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        game_state.turn.step = Step::PrecombatMain;
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn shifting_woodland_with_delirium_copies_a_creature_that_can_attack() {
+        let mut game_state = GameState::for_test(Vec::new());
+        for card in [
+            Card::Land(Land::Forest),
+            Card::Spell(Spell::Permanent(Permanent::PrimevalTitan)),
+            Card::Spell(Spell::Permanent(Permanent::AmuletOfVigor)),
+            Card::Spell(Spell::NonPermanent(NonPermanent::Sorcery(Sorcery::Explore))),
+        ] {
+            game_state.active_player.graveyard.add_card(card);
+        }
+        let woodland = game_state.add_test_land(Land::ShiftingWoodland, TapState::Untapped);
+        game_state.active_player.mana_pool = ManaPool { green: 2, colorless: 2, ..ManaPool::default() };
+        let before = game_state.snapshot();
+
+        let titan = Card::Spell(Spell::Permanent(Permanent::PrimevalTitan));
+        let copy = GameAction::ActivateAbility {
+            source: AbilitySource::Battlefield(woodland),
+            target: Some(Target::Card(titan)),
+        };
+        let mut results = vec![copy.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        assert_eq!(game_state.active_player.battlefield.lands[&woodland].card(), titan);
+        game_state.turn.step = Step::Combat;
+        results.push(GameAction::DeclareAttackers(vec![woodland]).apply(&mut game_state));
+        assert_eq!(game_state.active_player.battlefield.tap_state(woodland), Some(TapState::Tapped));
+        assert_eq!(game_state.stack.objects, vec![StackObject::Trigger(Trigger::Attacks(titan))]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        game_state.turn.step = Step::PrecombatMain;
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn crumbling_vestige_enters_tapped_and_adds_one_mana_of_any_color() {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.active_player.hand.lands.push(Land::CrumblingVestige);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PlayLand(Land::CrumblingVestige).apply(&mut game_state)];
+        let vestige = *game_state.active_player.battlefield.lands.keys().max().unwrap();
+        assert_eq!(game_state.active_player.battlefield.tap_state(vestige), Some(TapState::Tapped));
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        assert_eq!(game_state.decision.clone().unwrap().options(&game_state).len(), 5);
+        results.push(GameAction::Choose(Choice::Color(ManaType::Red)).apply(&mut game_state));
+        assert_eq!(game_state.active_player.mana_pool, ManaPool::of(ManaType::Red, 1));
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn mycosynth_gardens_becomes_another_amulet() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let amulet = game_state.add_test_permanent(Permanent::AmuletOfVigor);
+        let gardens = game_state.add_test_land(Land::TheMycosynthGardens, TapState::Untapped);
+        game_state.active_player.mana_pool = ManaPool::of(ManaType::Colorless, 1);
+        let before = game_state.snapshot();
+
+        let copy = GameAction::ActivateAbility {
+            source: AbilitySource::Battlefield(gardens),
+            target: Some(Target::Object(amulet)),
+        };
+        let results = [copy.apply(&mut game_state), GameAction::PassPriority.apply(&mut game_state)];
+        assert_eq!(game_state.amulets(), vec![amulet, gardens]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+
+    #[test]
+    fn lotus_field_enters_tapped_and_sacrifices_two_untapped_lands() {
+        let mut game_state = GameState::for_test(Vec::new());
+        let forest = game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.add_test_land(Land::Forest, TapState::Untapped);
+        let chamber = game_state.add_test_land(Land::SimicGrowthChamber, TapState::Untapped);
+        game_state.add_test_land(Land::GruulTurf, TapState::Tapped);
+        game_state.active_player.hand.lands.push(Land::LotusField);
+        let before = game_state.snapshot();
+
+        let mut results = vec![GameAction::PlayLand(Land::LotusField).apply(&mut game_state)];
+        let lotus = *game_state.active_player.battlefield.lands.keys().max().unwrap();
+        assert_eq!(game_state.active_player.battlefield.tap_state(lotus), Some(TapState::Tapped));
+        results.push(GameAction::PassPriority.apply(&mut game_state));
+        assert_eq!(game_state.decision.clone().unwrap().options(&game_state).len(), 2);
+
+        // Choosing too few lands still sacrifices two
+        results.push(GameAction::Choose(Choice::Objects(vec![chamber])).apply(&mut game_state));
+        let lands = &game_state.active_player.battlefield.lands;
+        assert!(!lands.contains_key(&chamber) && !lands.contains_key(&forest));
+        assert_eq!(lands.len(), 3);
+        assert_eq!(game_state.active_player.graveyard.lands, vec![Land::SimicGrowthChamber, Land::Forest]);
+
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.snapshot(), before);
+    }
+}
@@ -0,0 +1,411 @@
+// Monte Carlo goldfish harness for Magic: The Gathering Amulet Titan simulation
+
+use crate::cards::{Card, Instant, Land, NonPermanent, Permanent, Sorcery, Spell};
+use crate::game_action::GameAction;
+use crate::game_state::{
+    Battlefield, GameOutcome, GameState, Graveyard, Hand, Library, ManaPool, Player, PlayerId, Stack, Turn,
+};
+use crate::solver::{Goal, SolveResult, Solver};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// ============================================================================
+// DECKLIST
+// ============================================================================
+
+/// Number of cards in an opening hand
+pub const OPENING_HAND_SIZE: usize = 7;
+
+/// Expands a decklist of card counts into its cards
+pub fn decklist(entries: &[(usize, Card)]) -> Vec<Card> {
+    entries
+        .iter()
+        .flat_map(|&(count, card)| std::iter::repeat_n(card, count))
+        .collect()
+}
+
+/// A 60 card Amulet Titan list made of the cards the engine knows
+pub fn amulet_titan() -> Vec<Card> {
+    let permanent = |permanent| Card::Spell(Spell::Permanent(permanent));
+    let sorcery = |sorcery| Card::Spell(Spell::NonPermanent(NonPermanent::Sorcery(sorcery)));
+    decklist(&[
+        // Spells
+        (4, permanent(Permanent::AmuletOfVigor)),
+        (4, permanent(Permanent::PrimevalTitan)),
+        (4, permanent(Permanent::ArborealGrazer)),
+        (2, permanent(Permanent::Spelunking)),
+        (2, permanent(Permanent::CultivatorColossus)),
+        (2, permanent(Permanent::AftermathAnalyst)),
+        (4, Card::Spell(Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact)))),
+        (4, sorcery(Sorcery::Explore)),
+        (1, sorcery(Sorcery::Scapeshift)),
+        (1, sorcery(Sorcery::GreenSunsZenith)),
+        // Lands
+        (8, Card::Land(Land::Forest)),
+        (4, Card::Land(Land::SimicGrowthChamber)),
+        (2, Card::Land(Land::GruulTurf)),
+        (4, Card::Land(Land::UrzasSaga)),
+        (2, Card::Land(Land::Vesuva)),
+        (2, Card::Land(Land::TolariaWest)),
+        (1, Card::Land(Land::LotusField)),
+        (1, Card::Land(Land::EchoingDeeps)),
+        (1, Card::Land(Land::UrzasCave)),
+        (1, Card::Land(Land::HanweirBattlements)),
+        (1, Card::Land(Land::CrumblingVestige)),
+        (1, Card::Land(Land::ShiftingWoodland)),
+        (1, Card::Land(Land::TheMycosynthGardens)),
+        (1, Card::Land(Land::BoseijuWhoEndures)),
+        (1, Card::Land(Land::OtawaraSoaringCity)),
+        (1, Card::Land(Land::Mirrorpool)),
+    ])
+}
+
+/// Creates a player with the given library and nothing else
+fn empty_player(library: Library) -> Player {
+    Player {
+        life_total: 20,
+        library,
+        hand: Hand::default(),
+        battlefield: Battlefield::default(),
+        graveyard: Graveyard::default(),
+        mana_pool: ManaPool::default(),
+    }
+}
+
+/// Shuffles the deck with the seeded library, deals an opening hand and sets
+/// up the first turn against an opponent who does nothing. The rest of the
+/// library keeps the shuffled order, so every draw is known to the solver.
+pub fn deal(deck: &[Card], seed: u64, on_the_play: bool) -> GameState {
+    let mut shuffled = Library::new(deck.to_vec(), seed);
+    let order: Vec<Card> = std::iter::from_fn(|| shuffled.draw_card()).collect();
+    let (opening_hand, library) = order.split_at(OPENING_HAND_SIZE.min(order.len()));
+
+    let mut player = empty_player(Library::ordered(library.to_vec()));
+    opening_hand.iter().for_each(|&card| player.hand.add_card(card));
+    player.battlefield.land_plays = 1;
+
+    GameState {
+        active_player: player,
+        non_active_player: Some(empty_player(Library::new(Vec::new(), seed))),
+        stack: Stack::default(),
+        priority: PlayerId::Active,
+        next_id: 0,
+        decision: None,
+        priority_passes: 0,
+        turn: Turn::first(on_the_play),
+        delayed_triggers: Vec::new(),
+        outcome: None,
+    }
+}
+
+// ============================================================================
+// POLICIES
+// ============================================================================
+
+/// What a policy does next
+#[derive(Debug, Clone, PartialEq)]
+pub enum Play {
+    Action(GameAction),
+    /// There's no way to meet the goal
+    Concede,
+    /// The policy ran out of effort before it could tell
+    GiveUp,
+}
+
+/// Decides what the simulated player does
+pub trait Policy {
+    /// Forgets anything kept from the previous game; called before each game
+    fn new_game(&mut self) {}
+
+    /// Picks what to do next. The game state must be left as it was.
+    fn choose(&mut self, game_state: &mut GameState) -> Play;
+}
+
+/// Plays the fastest line the solver finds with perfect knowledge of the
+/// draws, conceding if there's no win and giving up if the solver does
+pub struct SolverPolicy {
+    solver: Solver,
+    /// The rest of the planned line, next action last
+    plan: Vec<GameAction>,
+}
+
+impl SolverPolicy {
+    pub fn new(solver: Solver) -> Self {
+        SolverPolicy {
+            solver,
+            plan: Vec::new(),
+        }
+    }
+}
+
+impl Policy for SolverPolicy {
+    fn new_game(&mut self) {
+        self.plan.clear();
+    }
+
+    fn choose(&mut self, game_state: &mut GameState) -> Play {
+        if self.plan.is_empty() {
+            match self.solver.solve(game_state) {
+                SolveResult::Solved(solution) => self.plan = solution.actions.into_iter().rev().collect(),
+                SolveResult::Unsolvable => return Play::Concede,
+                SolveResult::GaveUp => return Play::GiveUp,
+            }
+        }
+        self.plan.pop().map_or(Play::Concede, Play::Action)
+    }
+}
+
+/// Picks uniformly among the legal actions, as a baseline
+pub struct RandomPolicy {
+    rng: StdRng,
+}
+
+impl RandomPolicy {
+    pub fn new(seed: u64) -> Self {
+        RandomPolicy {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Policy for RandomPolicy {
+    fn choose(&mut self, game_state: &mut GameState) -> Play {
+        let actions = game_state.legal_actions();
+        if actions.is_empty() {
+            return Play::Concede;
+        }
+        let index = self.rng.gen_range(0..actions.len());
+        Play::Action(actions[index].clone())
+    }
+}
+
+// ============================================================================
+// SIMULATION
+// ============================================================================
+
+/// Settings for a batch of goldfish games
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub deck: Vec<Card>,
+    pub games: usize,
+    pub on_the_play: bool,
+    pub goal: Goal,
+    /// Games that haven't met the goal by the end of this turn count as no kill
+    pub max_turn: usize,
+    /// Game `i` is dealt with seed `seed + i`
+    pub seed: u64,
+}
+
+impl Simulation {
+    /// Plays every game with the policy and collects the results
+    pub fn run(&self, policy: &mut dyn Policy) -> Report {
+        let results = (0..self.games as u64)
+            .map(|game| {
+                let mut game_state = deal(&self.deck, self.seed.wrapping_add(game), self.on_the_play);
+                policy.new_game();
+                self.play(&mut game_state, policy)
+            })
+            .collect();
+        Report { results }
+    }
+
+    /// Plays one game to its result
+    pub fn play(&self, game_state: &mut GameState, policy: &mut dyn Policy) -> GameResult {
+        loop {
+            if self.goal.is_met(game_state) {
+                return GameResult::Kill(game_state.turn.number);
+            }
+            if let Some(GameOutcome::Lost(_)) = game_state.outcome {
+                return GameResult::Lost;
+            }
+            if game_state.turn.number > self.max_turn || game_state.outcome.is_some() {
+                return GameResult::NoKill;
+            }
+            match policy.choose(game_state) {
+                Play::Action(action) => {
+                    action.apply(game_state);
+                }
+                Play::Concede => return GameResult::NoKill,
+                Play::GiveUp => return GameResult::GaveUp,
+            }
+        }
+    }
+}
+
+// ============================================================================
+// REPORT
+// ============================================================================
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    /// The goal was met on this turn
+    Kill(usize),
+    /// The goal wasn't met by the turn limit
+    NoKill,
+    /// The game was lost, such as to an unpaid Summoner's Pact
+    Lost,
+    /// The policy gave up before it could tell
+    GaveUp,
+}
+
+impl GameResult {
+    /// Returns the kill turn of a game that met the goal
+    pub fn kill_turn(self) -> Option<usize> {
+        match self {
+            GameResult::Kill(turn) => Some(turn),
+            _ => None,
+        }
+    }
+}
+
+/// The results of a simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// How each game ended
+    pub results: Vec<GameResult>,
+}
+
+impl Report {
+    /// Returns how many games met the goal on each turn
+    pub fn distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for turn in self.results.iter().filter_map(|result| result.kill_turn()) {
+            *distribution.entry(turn).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// Returns the number of games that ended with the result
+    pub fn count(&self, result: GameResult) -> usize {
+        self.results.iter().filter(|&&r| r == result).count()
+    }
+
+    /// Returns the games the policy didn't give up on
+    fn decided(&self) -> Vec<GameResult> {
+        self.results.iter().copied().filter(|&result| result != GameResult::GaveUp).collect()
+    }
+
+    /// Returns the average kill turn of the games that met the goal
+    pub fn average(&self) -> Option<f64> {
+        let kills: Vec<usize> = self.results.iter().filter_map(|result| result.kill_turn()).collect();
+        (!kills.is_empty()).then(|| kills.iter().sum::<usize>() as f64 / kills.len() as f64)
+    }
+
+    /// Returns the turn by which `percent` of the decided games had met the
+    /// goal, or None if that many never did. Games the policy gave up on are
+    /// left out, as there's no telling how they would have gone.
+    pub fn percentile(&self, percent: f64) -> Option<usize> {
+        let mut turns: Vec<Option<usize>> = self.decided().into_iter().map(GameResult::kill_turn).collect();
+        // Games without a kill sort after every kill turn
+        turns.sort_by_key(|turn| turn.unwrap_or(usize::MAX));
+        let rank = (percent / 100.0 * turns.len() as f64).ceil() as usize;
+        turns.get(rank.saturating_sub(1)).copied().flatten()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let games = self.results.len().max(1) as f64;
+        let share = |count: usize| count as f64 * 100.0 / games;
+        writeln!(f, "Games: {}", self.results.len())?;
+        for (turn, count) in self.distribution() {
+            writeln!(f, "  Turn {:>2}: {:>6} ({:5.1}%)", turn, count, share(count))?;
+        }
+        for (label, result) in [
+            ("No kill", GameResult::NoKill),
+            ("Lost", GameResult::Lost),
+            ("Gave up", GameResult::GaveUp),
+        ] {
+            let count = self.count(result);
+            writeln!(f, "  {:<8}{:>6} ({:5.1}%)", format!("{}:", label), count, share(count))?;
+        }
+        match self.average() {
+            Some(average) => writeln!(f, "Average kill turn: {:.2}", average)?,
+            None => writeln!(f, "Average kill turn: -")?,
+        }
+        for percent in [25.0, 50.0, 75.0, 90.0] {
+            match self.percentile(percent) {
+                Some(turn) => writeln!(f, "  {}th percentile: turn {}", percent, turn)?,
+                None => writeln!(f, "  {}th percentile: no kill", percent)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Always takes the first legal action
+    struct FirstAction;
+
+    impl Policy for FirstAction {
+        fn choose(&mut self, game_state: &mut GameState) -> Play {
+            game_state.legal_actions().into_iter().next().map_or(Play::Concede, Play::Action)
+        }
+    }
+
+    fn simulation(goal: Goal) -> Simulation {
+        Simulation {
+            deck: vec![Card::Land(Land::Forest); 20],
+            games: 1,
+            on_the_play: true,
+            goal,
+            max_turn: 3,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn an_unpaid_pact_loses_the_game() {
+        let simulation = simulation(Goal::TitanAttack);
+        let mut game_state = deal(&simulation.deck, 0, true);
+        let pact = Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact));
+        game_state.active_player.hand = Hand::new(Vec::new(), vec![pact]);
+        assert_eq!(simulation.play(&mut game_state, &mut FirstAction), GameResult::Lost);
+    }
+
+    #[test]
+    fn the_solver_policy_gives_up_when_the_solver_does() {
+        let simulation = simulation(Goal::Lethal);
+        let mut policy = SolverPolicy::new(Solver::new(Goal::Lethal, 3).with_node_limit(1));
+        assert_eq!(simulation.run(&mut policy).results, vec![GameResult::GaveUp]);
+
+        let mut policy = SolverPolicy::new(Solver::new(Goal::Lethal, 3));
+        assert_eq!(simulation.run(&mut policy).results, vec![GameResult::NoKill]);
+    }
+
+    #[test]
+    fn the_solver_policy_forgets_its_plan_between_games() {
+        let mut policy = SolverPolicy::new(Solver::new(Goal::Lethal, 3));
+        policy.plan = vec![GameAction::PassPriority];
+        policy.new_game();
+        assert!(policy.plan.is_empty());
+    }
+
+    #[test]
+    fn reports_losses_and_give_ups_apart_from_games_without_a_kill() {
+        let report = Report {
+            results: vec![
+                GameResult::Kill(2),
+                GameResult::Kill(3),
+                GameResult::NoKill,
+                GameResult::Lost,
+                GameResult::GaveUp,
+            ],
+        };
+        assert_eq!(report.count(GameResult::NoKill), 1);
+        assert_eq!(report.count(GameResult::Lost), 1);
+        assert_eq!(report.count(GameResult::GaveUp), 1);
+        assert_eq!(report.average(), Some(2.5));
+        // The game given up on is left out of the percentiles
+        assert_eq!(report.percentile(50.0), Some(3));
+        assert_eq!(report.percentile(75.0), None);
+        let text = report.to_string();
+        assert!(text.contains("Lost:") && text.contains("Gave up:"));
+    }
+}
@@ -0,0 +1,354 @@
+// Goldfish solver for Magic: The Gathering Amulet Titan simulation
+
+use crate::cards::{Card, HasManaValue, Permanent, Spell};
+use crate::game_action::GameAction;
+use crate::game_state::{
+    AbilitySource, Decision, GameObjectId, GameOutcome, GameState, PlayerId, StackObject, Step, Target, Trigger,
+};
+use crate::zobrist::object_key;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+// ============================================================================
+// GOALS
+// ============================================================================
+
+/// What the solver counts as a win
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// Primeval Titan attacks, either with haste or after surviving a turn
+    TitanAttack,
+    /// Combat damage takes the opponent to zero life
+    Lethal,
+}
+
+impl Goal {
+    /// Returns true if the game state meets the goal
+    pub fn is_met(self, game_state: &GameState) -> bool {
+        match self {
+            Goal::TitanAttack => {
+                let titan = Card::Spell(Spell::Permanent(Permanent::PrimevalTitan));
+                game_state
+                    .active_player
+                    .battlefield
+                    .attacking()
+                    .into_iter()
+                    .any(|id| game_state.permanent(id) == Some(titan))
+            }
+            Goal::Lethal => game_state.outcome == Some(GameOutcome::Won),
+        }
+    }
+}
+
+// ============================================================================
+// SOLVER
+// ============================================================================
+
+/// The earliest win found and the actions that reach it from the starting state
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub turn: usize,
+    pub actions: Vec<GameAction>,
+}
+
+/// What a solve found
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveResult {
+    /// The earliest win and a line that reaches it
+    Solved(Solution),
+    /// No line meets the goal by the turn limit
+    Unsolvable,
+    /// The node limit ran out before the search could tell
+    GaveUp,
+}
+
+/// Depth-first search over the legal actions for the earliest turn the goal
+/// can be met. Draws past the known top of the library are random, so the
+/// state should have a fully ordered library (see Library::ordered).
+pub struct Solver {
+    goal: Goal,
+    max_turn: usize,
+    line: Vec<GameAction>,
+    /// The turn the goal was met on at the end of `line`
+    met_on: usize,
+    nodes: usize,
+    /// Gives up on a solve after visiting this many states
+    node_limit: Option<usize>,
+    /// Positions already searched without meeting the goal under the current
+    /// turn limit, by position_key
+    transpositions: HashSet<u64>,
+}
+
+impl Solver {
+    /// Creates a solver that gives up after `max_turn`
+    pub fn new(goal: Goal, max_turn: usize) -> Self {
+        Solver {
+            goal,
+            max_turn,
+            line: Vec::new(),
+            met_on: 0,
+            nodes: 0,
+            node_limit: None,
+            transpositions: HashSet::new(),
+        }
+    }
+
+    /// Limits each solve to visiting `node_limit` states. A solve that runs out
+    /// gives up, even if a solution exists.
+    pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = Some(node_limit);
+        self
+    }
+
+    /// Returns the number of states visited by the last solve
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Finds the earliest turn the goal can be met and a line of actions that
+    /// meets it. Each turn limit is searched in order, so the first line found
+    /// is the fastest. The game state is left as it was.
+    pub fn solve(&mut self, game_state: &mut GameState) -> SolveResult {
+        self.nodes = 0;
+        self.line.clear();
+        for turn in game_state.turn.number..=self.max_turn {
+            self.transpositions.clear();
+            if self.search(game_state, turn) {
+                let actions = std::mem::take(&mut self.line);
+                return SolveResult::Solved(Solution { turn: self.met_on, actions });
+            }
+            if self.out_of_nodes() {
+                return SolveResult::GaveUp;
+            }
+        }
+        SolveResult::Unsolvable
+    }
+
+    /// Returns true if the node limit has run out
+    fn out_of_nodes(&self) -> bool {
+        self.node_limit.is_some_and(|limit| self.nodes > limit)
+    }
+
+    /// Searches every line that stays within `turn_limit`, leaving the winning
+    /// line in `self.line` if there is one
+    fn search(&mut self, game_state: &mut GameState, turn_limit: usize) -> bool {
+        self.nodes += 1;
+        if game_state.turn.number > turn_limit || self.out_of_nodes() {
+            return false;
+        }
+        if self.goal.is_met(game_state) {
+            self.met_on = game_state.turn.number;
+            return true;
+        }
+        if self.past_combat(game_state, turn_limit) {
+            return false;
+        }
+        // The same position reached by another ordering has the same outcome
+        if !self.transpositions.insert(position_key(game_state)) {
+            return false;
+        }
+        let actions = game_state.legal_actions();
+        for action in &actions {
+            if is_redundant(game_state, action, &actions) {
+                continue;
+            }
+            let result = action.apply(game_state);
+            self.line.push(action.clone());
+            let found = self.search(game_state, turn_limit);
+            result.revert(game_state);
+            if found {
+                return true;
+            }
+            self.line.pop();
+        }
+        false
+    }
+
+    /// Returns true if the last turn's combat is over. Both goals can only be
+    /// met in combat, so nothing after it can reach them: Titan attacking once
+    /// attackers are declared, and lethal damage as combat ends.
+    fn past_combat(&self, game_state: &GameState, turn_limit: usize) -> bool {
+        let turn = &game_state.turn;
+        let attacked = self.goal == Goal::TitanAttack && turn.attackers_declared;
+        turn.number == turn_limit
+            && (attacked || matches!(turn.step, Step::PostcombatMain | Step::End | Step::Cleanup))
+    }
+}
+
+/// Returns true if the action can be skipped because another of the legal
+/// `actions` does the same thing at least as well. With the stack empty and no
+/// decision pending:
+/// - passing with mana in the pool wastes it while something could still
+///   spend it, and tapping lands for mana that then empties is almost never
+///   better than not tapping them
+/// - goldfishing, anything done outside the main phases can be done in the
+///   precombat main phase instead, or after attacking in the postcombat one
+fn is_redundant(game_state: &GameState, action: &GameAction, actions: &[GameAction]) -> bool {
+    if game_state.priority != PlayerId::Active
+        || !game_state.stack.objects.is_empty()
+        || game_state.decision.is_some()
+    {
+        return false;
+    }
+    match action {
+        GameAction::PassPriority => {
+            !game_state.active_player.mana_pool.is_empty()
+                && actions
+                    .iter()
+                    .any(|other| spends_mana(game_state, other) && !is_redundant(game_state, other, actions))
+        }
+        GameAction::DeclareAttackers(_) => false,
+        _ => match game_state.turn.step {
+            Step::PrecombatMain => false,
+            Step::PostcombatMain => !game_state.turn.attackers_declared,
+            _ => true,
+        },
+    }
+}
+
+/// Returns true if the action pays mana from the pool. Free spells like
+/// Summoner's Pact don't count, as casting one doesn't use the floating mana.
+fn spends_mana(game_state: &GameState, action: &GameAction) -> bool {
+    match action {
+        GameAction::CastSpell { spell, x } => spell.mana_value().converted() > 0 || *x > 0,
+        GameAction::ActivateAbility { source, .. } => game_state
+            .activated_ability(*source)
+            .is_some_and(|activated| activated.cost.converted() > 0),
+        _ => false,
+    }
+}
+
+/// Returns the key a position is stored under in the transposition table: its
+/// Zobrist hash together with the turn, priority, pending decision and the
+/// other small parts of the state the Zobrist hash leaves out. The battlefield
+/// hash ignores ids, so the permanents the stack and decision refer to by id
+/// are hashed as well.
+fn position_key(game_state: &GameState) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    game_state.zobrist_hash().hash(&mut hasher);
+    for id in referenced_objects(game_state) {
+        game_state.active_player.battlefield.get(id).as_ref().map(object_key).hash(&mut hasher);
+    }
+    game_state.turn.hash(&mut hasher);
+    game_state.priority.hash(&mut hasher);
+    game_state.priority_passes.hash(&mut hasher);
+    game_state.decision.hash(&mut hasher);
+    game_state.delayed_triggers.hash(&mut hasher);
+    game_state.outcome.hash(&mut hasher);
+    game_state.active_player.library.len().hash(&mut hasher);
+    game_state
+        .non_active_player
+        .as_ref()
+        .map(|opponent| opponent.life_total)
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the ids of the permanents referred to by the stack, the pending
+/// decision and the delayed triggers, in that order
+fn referenced_objects(game_state: &GameState) -> Vec<GameObjectId> {
+    let trigger_object = |trigger: &Trigger| match *trigger {
+        Trigger::AmuletUntap(id) | Trigger::Chapter { source: id, .. } | Trigger::EntersAsCopy(id) => Some(id),
+        Trigger::Enters(_) | Trigger::Attacks(_) | Trigger::PactPayment => None,
+    };
+    let mut ids = Vec::new();
+    for object in &game_state.stack.objects {
+        match object {
+            StackObject::Spell { .. } => {}
+            StackObject::Trigger(trigger) => ids.extend(trigger_object(trigger)),
+            StackObject::ActivatedAbility { source, target, .. } => {
+                if let AbilitySource::Battlefield(id) = source {
+                    ids.push(*id);
+                }
+                if let Some(Target::Object(id)) = target {
+                    ids.push(*id);
+                }
+            }
+        }
+    }
+    if let Some(Decision::CopyLand { source } | Decision::CopyLandFromGraveyard { source }) = game_state.decision {
+        ids.push(source);
+    }
+    ids.extend(game_state.delayed_triggers.iter().filter_map(trigger_object));
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Instant, Land, NonPermanent};
+    use crate::game_action::PrimitiveGameAction;
+    use crate::game_state::{GameObject, Library, ManaPool, TapState};
+    use crate::mana::ManaType;
+
+    /// A Primeval Titan on the battlefield in the first main phase with an
+    /// ordered library of Forests, against an opponent at `life`
+    fn titan(life: isize, haste: bool) -> GameState {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.active_player.library = Library::ordered(vec![Card::Land(Land::Forest); 20]);
+        let mut opponent = game_state.active_player.clone();
+        opponent.life_total = life;
+        game_state.non_active_player = Some(opponent);
+        let mut titan = GameObject::new(Card::Spell(Spell::Permanent(Permanent::PrimevalTitan)), TapState::Untapped);
+        titan.entered_turn = game_state.turn.number;
+        titan.haste = haste;
+        let id = game_state.next_game_object_id();
+        game_state.active_player.battlefield.insert(id, titan);
+        game_state
+    }
+
+    /// Solves for the goal and checks the line found meets it on that turn
+    fn solve(game_state: &mut GameState, goal: Goal) -> Option<usize> {
+        let SolveResult::Solved(solution) = Solver::new(goal, 3).solve(game_state) else {
+            return None;
+        };
+        let results: Vec<_> = solution.actions.iter().map(|action| action.apply(game_state)).collect();
+        assert!(goal.is_met(game_state));
+        assert_eq!(game_state.turn.number, solution.turn);
+        results.into_iter().rev().for_each(|result| result.revert(game_state));
+        Some(solution.turn)
+    }
+
+    #[test]
+    fn positions_differ_by_the_permanents_the_stack_refers_to() {
+        let position = |tapped: usize| {
+            let mut game_state = GameState::for_test(Vec::new());
+            let tap_state = |index| if index == tapped { TapState::Tapped } else { TapState::Untapped };
+            let first = game_state.add_test_land(Land::Forest, tap_state(0));
+            game_state.add_test_land(Land::Forest, tap_state(1));
+            game_state.stack.push(StackObject::Trigger(Trigger::AmuletUntap(first)));
+            position_key(&game_state)
+        };
+        assert_ne!(position(0), position(1));
+    }
+
+    #[test]
+    fn finds_lethal_on_the_turn_it_happens() {
+        assert_eq!(solve(&mut titan(6, true), Goal::Lethal), Some(1));
+        assert_eq!(solve(&mut titan(12, true), Goal::Lethal), Some(2));
+    }
+
+    #[test]
+    fn attacks_with_mana_left_that_cannot_be_spent() {
+        let mut game_state = titan(20, true);
+        PrimitiveGameAction::AddMana(ManaPool::of(ManaType::Green, 1)).apply(&mut game_state);
+        assert_eq!(solve(&mut game_state, Goal::TitanAttack), Some(1));
+    }
+
+    #[test]
+    fn does_not_cast_a_free_pact_to_use_up_floating_mana() {
+        let mut game_state = titan(20, false);
+        let pact = Spell::NonPermanent(NonPermanent::Instant(Instant::SummonersPact));
+        game_state.active_player.hand.spells.push(pact);
+        PrimitiveGameAction::AddMana(ManaPool::of(ManaType::Green, 1)).apply(&mut game_state);
+        assert_eq!(solve(&mut game_state, Goal::TitanAttack), Some(2));
+    }
+
+    #[test]
+    fn tells_unsolvable_states_apart_from_giving_up() {
+        assert_eq!(Solver::new(Goal::Lethal, 3).solve(&mut titan(100, true)), SolveResult::Unsolvable);
+        let mut solver = Solver::new(Goal::Lethal, 3).with_node_limit(5);
+        assert_eq!(solver.solve(&mut titan(12, true)), SolveResult::GaveUp);
+    }
+}
@@ -0,0 +1,219 @@
+// Zobrist hashing for Magic: The Gathering Amulet Titan simulation
+
+use crate::cards::Card;
+use crate::game_state::{GameObject, GameState, ManaPool, StackObject, TapState};
+use crate::mana::ManaType;
+use enum_map::EnumMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+// ============================================================================
+// KEYS
+// ============================================================================
+
+/// Random keys for each feature of the hashed zones. Zones are unordered, so
+/// the keys of their cards and objects are combined by wrapping addition,
+/// which unlike XOR doesn't cancel out duplicate copies.
+struct Keys {
+    hand: EnumMap<Card, u64>,
+    graveyard: EnumMap<Card, u64>,
+    permanent: EnumMap<Card, u64>,
+    copy_of: EnumMap<Card, u64>,
+    tapped: u64,
+    token: u64,
+    haste: u64,
+    attacking: u64,
+    copy_until_end_of_turn: u64,
+    lore_counters: [u64; 4],
+    entered_turn: [u64; 32],
+    mana: [u64; 6],
+    land_plays: u64,
+}
+
+/// Seed for the key tables, fixed so hashes are the same from run to run
+const SEED: u64 = 0x5eed_a71a_5717_a000;
+
+fn keys() -> &'static Keys {
+    static KEYS: OnceLock<Keys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        Keys {
+            hand: EnumMap::from_fn(|_| rng.gen()),
+            graveyard: EnumMap::from_fn(|_| rng.gen()),
+            permanent: EnumMap::from_fn(|_| rng.gen()),
+            copy_of: EnumMap::from_fn(|_| rng.gen()),
+            tapped: rng.gen(),
+            token: rng.gen(),
+            haste: rng.gen(),
+            attacking: rng.gen(),
+            copy_until_end_of_turn: rng.gen(),
+            lore_counters: rng.gen(),
+            entered_turn: rng.gen(),
+            mana: rng.gen(),
+            land_plays: rng.gen(),
+        }
+    })
+}
+
+// ============================================================================
+// FEATURE HASHES
+// ============================================================================
+
+/// Returns the key of a card in hand
+pub fn hand_key(card: Card) -> u64 {
+    keys().hand[card]
+}
+
+/// Returns the key of a card in the graveyard
+pub fn graveyard_key(card: Card) -> u64 {
+    keys().graveyard[card]
+}
+
+/// Returns the key of a permanent on the battlefield, covering everything
+/// about it except its id
+pub fn object_key(object: &GameObject<Card>) -> u64 {
+    let keys = keys();
+    let flag = |set: bool, key: u64| if set { key } else { 0 };
+    keys.permanent[object.permanent]
+        ^ object.copy_of.map_or(0, |card| keys.copy_of[card])
+        ^ flag(object.tap_state == TapState::Tapped, keys.tapped)
+        ^ flag(object.token, keys.token)
+        ^ flag(object.haste, keys.haste)
+        ^ flag(object.attacking, keys.attacking)
+        ^ flag(object.copy_until_end_of_turn, keys.copy_until_end_of_turn)
+        ^ keys.lore_counters[object.lore_counters as usize % keys.lore_counters.len()]
+        ^ keys.entered_turn[object.entered_turn % keys.entered_turn.len()]
+}
+
+/// Returns the key of an object at a depth of the stack. Stack objects are
+/// too varied for key tables, so each is hashed by value along with its depth.
+pub fn stack_key(object: &StackObject, depth: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    depth.hash(&mut hasher);
+    object.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the hash of a mana pool
+pub fn mana_pool_key(mana_pool: &ManaPool) -> u64 {
+    ManaType::ALL
+        .iter()
+        .zip(keys().mana)
+        .map(|(&mana_type, key)| key.wrapping_mul(mana_pool.get(mana_type) as u64))
+        .fold(0, u64::wrapping_add)
+}
+
+/// Returns the hash of the known top cards of the library. Their order
+/// decides the coming draws, so unlike the other zones it's hashed in order.
+pub fn library_top_key(top: &[Card]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    top.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the hash of the number of land plays left
+pub fn land_plays_key(land_plays: usize) -> u64 {
+    keys().land_plays.wrapping_mul(land_plays as u64)
+}
+
+// ============================================================================
+// GAME STATE HASH
+// ============================================================================
+
+impl GameState {
+    /// Returns the Zobrist hash of the active player's hand, battlefield,
+    /// graveyard, known library top, mana pool and land plays and of the
+    /// stack. The zones keep their hashes up to date as actions are applied
+    /// and reverted; the library top, mana pool and land plays are a handful
+    /// of cards and counts and are hashed here.
+    pub fn zobrist_hash(&self) -> u64 {
+        let player = &self.active_player;
+        [
+            player.hand.hash(),
+            player.battlefield.hash(),
+            player.graveyard.hash(),
+            library_top_key(&player.library.top),
+            mana_pool_key(&player.mana_pool),
+            land_plays_key(player.battlefield.land_plays),
+            self.stack.hash(),
+        ]
+        .into_iter()
+        .fold(0, u64::wrapping_add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::Land;
+    use crate::game_action::{GameAction, PrimitiveGameAction};
+    use crate::simulation::{amulet_titan, deal};
+
+    #[test]
+    fn permanents_hash_the_same_whatever_their_ids() {
+        let mut forest_first = GameState::for_test(Vec::new());
+        forest_first.add_test_land(Land::Forest, TapState::Untapped);
+        forest_first.add_test_land(Land::GruulTurf, TapState::Tapped);
+        let mut turf_first = GameState::for_test(Vec::new());
+        turf_first.add_test_land(Land::GruulTurf, TapState::Tapped);
+        let forest = turf_first.add_test_land(Land::Forest, TapState::Untapped);
+        assert_eq!(forest_first.zobrist_hash(), turf_first.zobrist_hash());
+
+        let tap = PrimitiveGameAction::SetTapState(forest, TapState::Tapped).apply(&mut turf_first);
+        assert_ne!(forest_first.zobrist_hash(), turf_first.zobrist_hash());
+        tap.revert(&mut turf_first);
+        assert_eq!(forest_first.zobrist_hash(), turf_first.zobrist_hash());
+    }
+
+    #[test]
+    fn the_known_library_top_is_hashed_in_order() {
+        let forest = Card::Land(Land::Forest);
+        let turf = Card::Land(Land::GruulTurf);
+        assert_ne!(library_top_key(&[forest, turf]), library_top_key(&[turf, forest]));
+    }
+
+    #[test]
+    fn reverting_restores_the_hash() {
+        let mut game_state = GameState::for_test(Vec::new());
+        game_state.add_test_land(Land::Forest, TapState::Untapped);
+        game_state.active_player.hand.add_card(Card::Land(Land::GruulTurf));
+        let before = game_state.zobrist_hash();
+
+        let results = [
+            GameAction::PlayLand(Land::GruulTurf).apply(&mut game_state),
+            GameAction::PassPriority.apply(&mut game_state),
+        ];
+        assert!(game_state.decision.is_some());
+        assert_ne!(game_state.zobrist_hash(), before);
+        for result in results.iter().rev() {
+            result.revert(&mut game_state);
+        }
+        assert_eq!(game_state.zobrist_hash(), before);
+    }
+
+    #[test]
+    fn reverting_every_legal_action_restores_the_state_and_hash() {
+        let deck = amulet_titan();
+        for seed in 0..50 {
+            let mut game_state = deal(&deck, seed, seed % 2 == 0);
+            let mut rng = StdRng::seed_from_u64(seed);
+            while game_state.turn.number <= 3 {
+                let actions = game_state.legal_actions();
+                if actions.is_empty() {
+                    break;
+                }
+                let before = (game_state.snapshot(), game_state.zobrist_hash());
+                for action in &actions {
+                    action.apply(&mut game_state).revert(&mut game_state);
+                    let after = (game_state.snapshot(), game_state.zobrist_hash());
+                    assert_eq!(after, before, "seed {} reverting {:?}", seed, action);
+                }
+                // Walk a random line so later turns get checked too
+                actions[rng.gen_range(0..actions.len())].apply(&mut game_state);
+            }
+        }
+    }
+}